@@ -13,6 +13,11 @@ use memory_lib::{
     span_example,
 };
 
+// This crate's safety summary, next to memory_lib's in the `.msafety`
+// section of the binary (`memory_audit embedded`)
+#[cfg(feature = "safety-summary")]
+include!(concat!(env!("OUT_DIR"), "/safety_summary.rs"));
+
 fn main() {
     println!("=== Rust Memory Safety Demo ===\n");
 
@@ -42,6 +47,8 @@ fn demonstrate_cross_module_propagation() {
     // let ptr = unsafe_alloc(5);  // ERROR: call to unsafe function
 
     // We MUST acknowledge the unsafety
+    // SAFETY DISCHARGE: count 5 > 0, offsets 0..3 < 5, written before read,
+    // freed once with the same count
     unsafe {
         let ptr = unsafe_alloc(5);
 
@@ -91,6 +98,7 @@ fn demonstrate_propagation_chain() {
     println!("Shows how unsafety chains through multiple levels.\n");
 
     // level3_propagate is unsafe - we must acknowledge
+    // SAFETY DISCHARGE: the pointer is released with cleanup, exactly once
     unsafe {
         let ptr = propagation_chain::level3_propagate();
         println!("level3_propagate() returned pointer (unsafe call)");
@@ -102,6 +110,7 @@ fn demonstrate_propagation_chain() {
     println!("level3_suppress() returned pointer (safe call)");
     // Note: we'd need unsafe to clean this up properly, which shows
     // the tension between propagation and suppression
+    // SAFETY DISCHARGE: ptr came from level3_suppress and is cleaned up once
     unsafe { propagation_chain::cleanup(ptr); }
 
     println!();
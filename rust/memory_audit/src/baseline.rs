@@ -0,0 +1,319 @@
+//! The unsafe-count ratchet.
+//!
+//! A package opts in by keeping a `safety-baseline.json` next to its
+//! `Cargo.toml`, recording the unsafe counts of every module and item in
+//! its `src/` that has any:
+//!
+//! ```json
+//! {
+//!   "modules": { "memory_lib": { "blocks": 10, "functions": 8, ... } },
+//!   "items": { "memory_lib::raw_alloc": { "functions": 1, ... } }
+//! }
+//! ```
+//!
+//! Any count above its baseline is a finding, so the unsafe footprint can
+//! only shrink. Code missing from the baseline counts as zero. Intended
+//! growth is recorded by rewriting the baseline with `--update-baseline`,
+//! which also locks in counts that went down.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::count::{self, UnsafeCounts};
+use crate::finding::{Finding, Rule};
+use crate::workspace;
+
+pub const FILE_NAME: &str = "safety-baseline.json";
+
+/// Recorded counts, keyed by module path and by item path. Only non-zero
+/// counts are kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    #[serde(default)]
+    pub modules: BTreeMap<String, UnsafeCounts>,
+    #[serde(default)]
+    pub items: BTreeMap<String, UnsafeCounts>,
+}
+
+/// The result of comparing the code with its baselines.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Ratchet {
+    /// Modules and items whose counts grew, sorted by file and line
+    pub findings: Vec<Finding>,
+    /// Modules and items now below their baseline, which an update would
+    /// lock in
+    pub shrunk: usize,
+}
+
+impl Baseline {
+    pub fn parse(text: &str) -> Result<Baseline, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("counts serialize");
+        json.push('\n');
+        json
+    }
+
+    /// The baseline of a counted package. Items sharing a path, such as
+    /// `cfg` variants of one function, are summed.
+    pub fn from_report(report: &count::Report) -> Baseline {
+        let mut baseline = Baseline::default();
+        for module in report.modules.iter().filter(|m| !m.counts.is_zero()) {
+            *baseline.modules.entry(module.path.clone()).or_default() += module.counts;
+        }
+        for item in &report.items {
+            *baseline.items.entry(item.path.clone()).or_default() += item.counts;
+        }
+        baseline
+    }
+}
+
+/// Compares every baseline in the workspace at `root` with the code.
+pub fn check(root: &Path) -> Result<Ratchet, String> {
+    let mut ratchet = Ratchet::default();
+    for tracked in tracked(root)? {
+        let found = compare(&tracked.rel_path, &tracked.baseline, &tracked.report);
+        ratchet.findings.extend(found.findings);
+        ratchet.shrunk += found.shrunk;
+    }
+    ratchet
+        .findings
+        .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(ratchet)
+}
+
+/// Rewrites every baseline in the workspace with the current counts.
+/// Returns the number of baselines that changed.
+pub fn update(root: &Path) -> Result<usize, String> {
+    let mut changed = 0;
+    for tracked in tracked(root)? {
+        let current = Baseline::from_report(&tracked.report);
+        if current != tracked.baseline {
+            fs::write(&tracked.path, current.to_json())
+                .map_err(|e| format!("{}: {}", tracked.rel_path, e))?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Compares one package's counts with its baseline, read from `rel_path`.
+pub fn compare(rel_path: &str, baseline: &Baseline, report: &count::Report) -> Ratchet {
+    let current = Baseline::from_report(report);
+    let mut ratchet = Ratchet::default();
+
+    // Where an item is: the first place it is counted
+    let mut places: BTreeMap<&str, (&str, usize)> = BTreeMap::new();
+    for item in &report.items {
+        places
+            .entry(item.path.as_str())
+            .or_insert((item.file.as_str(), item.line));
+    }
+    let mut grown_items = Vec::new();
+    for (path, counts) in &current.items {
+        let Some(growth) = growth(baseline.items.get(path), counts) else {
+            continue;
+        };
+        let (file, line) = places[path.as_str()];
+        grown_items.push((path, file, line));
+        ratchet.findings.push(Finding {
+            rule: Rule::UnsafeGrowth,
+            file: file.to_string(),
+            line,
+            item: path.clone(),
+            message: format!("unsafe code grew past {}: {}", rel_path, growth),
+            related: None,
+        });
+    }
+
+    for module in &report.modules {
+        let Some(growth) = growth(baseline.modules.get(&module.path), &module.counts) else {
+            continue;
+        };
+        // Point at the first item in it that grew
+        let line = grown_items
+            .iter()
+            .filter(|(path, file, _)| owner(report, path, file) == Some(&module.path))
+            .map(|(.., line)| *line)
+            .min()
+            .unwrap_or(1);
+        ratchet.findings.push(Finding {
+            rule: Rule::UnsafeGrowth,
+            file: module.file.clone(),
+            line,
+            item: module.path.clone(),
+            message: format!("unsafe code in module grew past {}: {}", rel_path, growth),
+            related: None,
+        });
+    }
+
+    let below = |recorded: &BTreeMap<String, UnsafeCounts>,
+                 now: &BTreeMap<String, UnsafeCounts>| {
+        recorded
+            .iter()
+            .filter(|(path, counts)| {
+                let now = now.get(*path).copied().unwrap_or_default();
+                fields(counts)
+                    .iter()
+                    .zip(fields(&now))
+                    .any(|((_, was), (_, is))| is < *was)
+            })
+            .count()
+    };
+    ratchet.shrunk =
+        below(&baseline.modules, &current.modules) + below(&baseline.items, &current.items);
+    ratchet
+        .findings
+        .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    ratchet
+}
+
+/// The module `item` is directly in: the longest module path prefixing it
+/// in the same file, so an item of an inline module is not its parent's.
+fn owner<'a>(report: &'a count::Report, item: &str, file: &str) -> Option<&'a String> {
+    report
+        .modules
+        .iter()
+        .filter(|m| m.file == file && item.starts_with(&format!("{}::", m.path)))
+        .map(|m| &m.path)
+        .max_by_key(|path| path.len())
+}
+
+/// The counts that grew, as `blocks 2 -> 3, impls 0 -> 1`.
+fn growth(recorded: Option<&UnsafeCounts>, counts: &UnsafeCounts) -> Option<String> {
+    let recorded = recorded.copied().unwrap_or_default();
+    let grown: Vec<String> = fields(&recorded)
+        .iter()
+        .zip(fields(counts))
+        .filter(|((_, was), (_, is))| is > was)
+        .map(|((name, was), (_, is))| format!("{} {} -> {}", name, was, is))
+        .collect();
+    (!grown.is_empty()).then(|| grown.join(", "))
+}
+
+fn fields(counts: &UnsafeCounts) -> [(&'static str, usize); 5] {
+    [
+        ("blocks", counts.blocks),
+        ("functions", counts.functions),
+        ("impls", counts.impls),
+        ("traits", counts.traits),
+        ("extern blocks", counts.extern_blocks),
+    ]
+}
+
+/// A package that keeps a baseline.
+struct Tracked {
+    /// Path of the baseline relative to the workspace root
+    rel_path: String,
+    path: PathBuf,
+    baseline: Baseline,
+    report: count::Report,
+}
+
+fn tracked(root: &Path) -> Result<Vec<Tracked>, String> {
+    let files = workspace::source_files(root)?;
+    let mut out = Vec::new();
+    for package in workspace::packages(root)? {
+        let path = package.dir.join(FILE_NAME);
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let rel_path = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let baseline = Baseline::parse(&text).map_err(|e| format!("{}: {}", rel_path, e))?;
+        let src = package.dir.join("src");
+        let package_files: Vec<_> = files
+            .iter()
+            .filter(|f| f.package == package.name && f.path.starts_with(&src))
+            .cloned()
+            .collect();
+        out.push(Tracked {
+            rel_path,
+            path,
+            baseline,
+            report: count::count(&package_files)?,
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::count::{ItemReport, ModuleReport, Report};
+    use crate::items::ItemKind;
+
+    fn report(blocks: usize, functions: usize) -> Report {
+        let counts = UnsafeCounts {
+            blocks,
+            functions,
+            ..UnsafeCounts::default()
+        };
+        Report {
+            items: vec![ItemReport {
+                path: "krate::raw::read".to_string(),
+                kind: ItemKind::Function,
+                file: "krate/src/raw.rs".to_string(),
+                line: 7,
+                counts,
+            }],
+            modules: vec![ModuleReport {
+                path: "krate::raw".to_string(),
+                file: "krate/src/raw.rs".to_string(),
+                counts,
+            }],
+            total: counts,
+        }
+    }
+
+    #[test]
+    fn test_only_growth_fails() {
+        let baseline = Baseline::from_report(&report(2, 1));
+        let same = compare("krate/safety-baseline.json", &baseline, &report(2, 1));
+        assert_eq!(same, Ratchet::default());
+
+        let grown = compare("krate/safety-baseline.json", &baseline, &report(3, 1));
+        let found: Vec<_> = grown
+            .findings
+            .iter()
+            .map(|f| (f.item.as_str(), f.line, f.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "krate::raw::read",
+                    7,
+                    "unsafe code grew past krate/safety-baseline.json: blocks 2 -> 3"
+                ),
+                (
+                    "krate::raw",
+                    7,
+                    "unsafe code in module grew past krate/safety-baseline.json: blocks 2 -> 3"
+                ),
+            ]
+        );
+
+        // Trading a block for an unsafe fn is still growth
+        let traded = compare("krate/safety-baseline.json", &baseline, &report(1, 2));
+        assert_eq!(traded.findings.len(), 2);
+        assert_eq!(traded.shrunk, 2);
+
+        let shrunk = compare("krate/safety-baseline.json", &baseline, &report(0, 0));
+        assert!(shrunk.findings.is_empty());
+        assert_eq!(shrunk.shrunk, 2);
+
+        assert_eq!(Baseline::parse(&baseline.to_json()).unwrap(), baseline);
+        assert_eq!(Baseline::parse("{}").unwrap(), Baseline::default());
+    }
+}
@@ -0,0 +1,65 @@
+//! Finding the `unsafe { }` blocks of a unit.
+
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+use crate::count::for_each_macro_expr;
+use crate::items::{self, Syntax, Unit};
+
+/// An outermost `unsafe { }` block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsafeBlock {
+    /// Line of the `unsafe` keyword
+    pub line: usize,
+    /// Last line of the block
+    pub end_line: usize,
+    /// First line of the innermost statement containing the block, which
+    /// is where a comment about the block goes
+    pub stmt_line: usize,
+    /// Token text of the block, for comparing bodies across revisions
+    pub body: String,
+}
+
+/// Lists the outermost unsafe blocks in `unit`'s body, in source order.
+///
+/// Blocks nested in another unsafe block are covered by the outer one and
+/// are not listed.
+pub fn unsafe_blocks(unit: &Unit<'_>) -> Vec<UnsafeBlock> {
+    let mut finder = Finder {
+        stmt_line: unit.line,
+        blocks: Vec::new(),
+    };
+    match unit.syntax {
+        Syntax::Fn { block, .. } => finder.visit_block(block),
+        Syntax::Init { expr, .. } => finder.visit_expr(expr),
+        _ => {}
+    }
+    finder.blocks
+}
+
+struct Finder {
+    stmt_line: usize,
+    blocks: Vec<UnsafeBlock>,
+}
+
+impl<'ast> Visit<'ast> for Finder {
+    fn visit_stmt(&mut self, node: &'ast syn::Stmt) {
+        let outer = self.stmt_line;
+        self.stmt_line = items::line(node.span());
+        visit::visit_stmt(self, node);
+        self.stmt_line = outer;
+    }
+
+    fn visit_expr_unsafe(&mut self, node: &'ast syn::ExprUnsafe) {
+        self.blocks.push(UnsafeBlock {
+            line: items::line(node.unsafe_token.span),
+            end_line: items::end_line(node.span()),
+            stmt_line: self.stmt_line,
+            body: items::tokens(&node.block),
+        });
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        for_each_macro_expr(node, |expr| self.visit_expr(expr));
+    }
+}
@@ -0,0 +1,178 @@
+//! Checking the repo's safety comment conventions.
+//!
+//! - Every `unsafe { }` block carries a `// SAFETY DISCHARGE:` comment,
+//!   directly above its statement or trailing on its line
+//! - Every `unsafe fn` has a `# Safety` doc section, except methods of
+//!   trait impls, whose contract is documented on the trait
+//! - Every `unsafe impl` has a `// SAFETY:` comment directly above it
+
+use crate::blocks;
+use crate::comments::{self, DISCHARGE, RATIONALE};
+use crate::finding::{Finding, Rule};
+use crate::items::{self, ItemKind, Unit};
+use crate::workspace::SourceFile;
+
+/// Checks every source file; findings are sorted by file and line.
+pub fn check(files: &[SourceFile]) -> Result<Vec<Finding>, String> {
+    let mut findings = Vec::new();
+    for file in files {
+        let (text, syntax) = file.parse_with_text()?;
+        findings.extend(check_file(
+            &file.rel_path,
+            &file.module_path,
+            &text,
+            &syntax,
+        ));
+    }
+    Ok(findings)
+}
+
+/// Checks one parsed file. `rel_path` is only used in the findings.
+pub fn check_file(
+    rel_path: &str,
+    module_path: &[String],
+    text: &str,
+    syntax: &syn::File,
+) -> Vec<Finding> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut findings = Vec::new();
+    let mut report = |unit: &Unit<'_>, rule, line, message: String| {
+        findings.push(Finding {
+            rule,
+            file: rel_path.to_string(),
+            line,
+            item: unit.path.clone(),
+            message,
+            related: None,
+        });
+    };
+
+    items::walk(syntax, module_path, &mut |unit| {
+        for block in blocks::unsafe_blocks(&unit) {
+            let commented = comments::find_marked(&lines, block.line, DISCHARGE)
+                .or_else(|| comments::find_marked(&lines, block.stmt_line, DISCHARGE));
+            if commented.is_none() {
+                report(
+                    &unit,
+                    Rule::MissingDischarge,
+                    block.line,
+                    "unsafe block has no `// SAFETY DISCHARGE:` comment".to_string(),
+                );
+            }
+        }
+
+        if unit.kind.is_fn()
+            && unit.is_unsafe
+            && unit.impl_trait.is_none()
+            && !has_safety_doc(&unit)
+        {
+            report(
+                &unit,
+                Rule::MissingSafetyDoc,
+                unit.line,
+                format!("unsafe fn `{}` has no `# Safety` doc section", unit.name),
+            );
+        }
+
+        if unit.kind == ItemKind::Impl
+            && unit.is_unsafe
+            && comments::find_marked(&lines, unit.start_line, RATIONALE).is_none()
+        {
+            report(
+                &unit,
+                Rule::MissingImplRationale,
+                unit.line,
+                format!("unsafe impl `{}` has no `// SAFETY:` comment", unit.name),
+            );
+        }
+    });
+
+    findings.sort_by_key(|f| f.line);
+    findings
+}
+
+/// True if the unit's doc comment has a `# Safety` heading.
+fn has_safety_doc(unit: &Unit<'_>) -> bool {
+    unit.attrs()
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(doc),
+                    ..
+                }) => Some(doc.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .any(|doc| doc.lines().any(|line| line.trim() == "# Safety"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(source: &str) -> Vec<(Rule, usize)> {
+        let syntax = syn::parse_file(source).unwrap();
+        check_file("lib.rs", &["krate".to_string()], source, &syntax)
+            .into_iter()
+            .map(|f| (f.rule, f.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_conforming_code_passes() {
+        let source = r#"
+/// Reads `p`.
+///
+/// # Safety
+/// - `p` is valid
+pub unsafe fn read(p: *const i32) -> i32 {
+    // SAFETY DISCHARGE: forwarded from our own contract
+    unsafe { *p }
+}
+
+fn wrapper(v: &i32) -> i32 {
+    // SAFETY DISCHARGE: references are valid pointers
+    let x = Some(v)
+        .map(|v| unsafe { read(v) });
+    x.unwrap() + unsafe { read(v) } // SAFETY DISCHARGE: as above
+}
+
+// SAFETY: no interior mutability
+unsafe impl Sync for Buf {}
+
+// SAFETY: forwards to the system allocator
+unsafe impl GlobalAlloc for Alloc {
+    unsafe fn alloc(&self) {}
+}
+"#;
+        assert_eq!(rules(source), []);
+    }
+
+    #[test]
+    fn test_reports_each_rule() {
+        let source = r#"
+pub unsafe fn read(p: *const i32) -> i32 {
+    *p
+}
+
+fn wrapper(v: &i32) -> i32 {
+    // Not a discharge
+    unsafe { read(v) }
+}
+
+/// Docs, but no rationale
+unsafe impl Sync for Buf {}
+"#;
+        assert_eq!(
+            rules(source),
+            [
+                (Rule::MissingSafetyDoc, 2),
+                (Rule::MissingDischarge, 8),
+                (Rule::MissingImplRationale, 12),
+            ]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Reading the comments around a line.
+//!
+//! `syn` drops plain `//` comments, but the repo's safety conventions live
+//! in them, so these helpers look at the source text directly.
+
+/// Marker for the comment on every `unsafe { }` block.
+pub const DISCHARGE: &str = "SAFETY DISCHARGE:";
+
+/// Marker for the rationale above every `unsafe impl`.
+pub const RATIONALE: &str = "SAFETY:";
+
+/// Finds a comment starting with `marker` that belongs to `line` (1-based):
+/// either trailing on that line, or in the run of `//` lines directly above
+/// it (attribute lines in between are allowed).
+///
+/// Returns the comment text from the marker to the end of the comment, with
+/// the `//` prefixes removed and lines joined by `\n`.
+pub fn find_marked(lines: &[&str], line: usize, marker: &str) -> Option<String> {
+    let current = *lines.get(line.checked_sub(1)?)?;
+    if let Some(at) = current.find("//") {
+        let text = strip_comment(&current[at..]);
+        if text.starts_with(marker) {
+            return Some(text.to_string());
+        }
+    }
+
+    let mut above = Vec::new();
+    for text in lines[..line - 1].iter().rev().map(|l| l.trim()) {
+        if text.starts_with("//") {
+            above.push(strip_comment(text));
+        } else if !text.starts_with("#[") {
+            break;
+        }
+    }
+    above.reverse();
+    let start = above.iter().position(|text| text.starts_with(marker))?;
+    Some(above[start..].join("\n"))
+}
+
+fn strip_comment(text: &str) -> &str {
+    text.trim_start_matches('/').trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_marked() {
+        let source = "\
+fn f() {
+    // Unrelated note
+    // SAFETY DISCHARGE: first line
+    // continues here
+    #[allow(unused_unsafe)]
+    let a = unsafe { g() };
+
+    let b = unsafe { g() }; // SAFETY DISCHARGE: trailing
+    let c = unsafe { g() };
+}";
+        let lines: Vec<&str> = source.lines().collect();
+        assert_eq!(
+            find_marked(&lines, 6, DISCHARGE).as_deref(),
+            Some("SAFETY DISCHARGE: first line\ncontinues here")
+        );
+        assert_eq!(
+            find_marked(&lines, 8, DISCHARGE).as_deref(),
+            Some("SAFETY DISCHARGE: trailing")
+        );
+        assert_eq!(find_marked(&lines, 9, DISCHARGE), None);
+        assert_eq!(find_marked(&lines, 6, RATIONALE), None);
+    }
+}
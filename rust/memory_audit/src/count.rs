@@ -0,0 +1,254 @@
+//! Counting unsafe code per item and per module.
+//!
+//! Counts come from syntax trees, so `unsafe` in comments, strings and doc
+//! examples is ignored, and `unsafe impl`/`unsafe trait` are seen. Macro
+//! arguments are parsed as expressions or statements where possible, so
+//! `println!("{}", unsafe { *p })` counts; macros whose input is not Rust
+//! syntax are skipped.
+
+use std::collections::BTreeMap;
+use std::ops::{Add, AddAssign};
+
+use serde::{Deserialize, Serialize};
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
+
+use crate::items::{self, ItemKind, Syntax, Unit};
+use crate::workspace::SourceFile;
+
+/// Unsafe constructs found in some piece of code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnsafeCounts {
+    /// `unsafe { }` blocks
+    pub blocks: usize,
+    /// `unsafe fn` declarations (functions, methods and trait methods)
+    pub functions: usize,
+    /// `unsafe impl` blocks
+    pub impls: usize,
+    /// `unsafe trait` declarations
+    pub traits: usize,
+    /// `extern` blocks
+    pub extern_blocks: usize,
+}
+
+impl UnsafeCounts {
+    pub fn total(&self) -> usize {
+        self.blocks + self.functions + self.impls + self.traits + self.extern_blocks
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.total() == 0
+    }
+}
+
+impl Add for UnsafeCounts {
+    type Output = UnsafeCounts;
+
+    fn add(self, other: UnsafeCounts) -> UnsafeCounts {
+        UnsafeCounts {
+            blocks: self.blocks + other.blocks,
+            functions: self.functions + other.functions,
+            impls: self.impls + other.impls,
+            traits: self.traits + other.traits,
+            extern_blocks: self.extern_blocks + other.extern_blocks,
+        }
+    }
+}
+
+impl AddAssign for UnsafeCounts {
+    fn add_assign(&mut self, other: UnsafeCounts) {
+        *self = *self + other;
+    }
+}
+
+/// Counts for one item that contains unsafe code.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ItemReport {
+    pub path: String,
+    pub kind: ItemKind,
+    pub file: String,
+    pub line: usize,
+    pub counts: UnsafeCounts,
+}
+
+/// Counts for one module, summed over its items.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ModuleReport {
+    pub path: String,
+    pub file: String,
+    pub counts: UnsafeCounts,
+}
+
+/// Counts for a whole workspace.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub items: Vec<ItemReport>,
+    pub modules: Vec<ModuleReport>,
+    pub total: UnsafeCounts,
+}
+
+/// Counts the unsafe code that `unit` itself declares or contains.
+///
+/// Methods are units of their own, so an impl or trait only counts its own
+/// `unsafe` keyword, not the unsafe code in its methods.
+pub fn count_unit(unit: &Unit<'_>) -> UnsafeCounts {
+    let mut counts = UnsafeCounts::default();
+    match unit.syntax {
+        Syntax::Fn { block, .. } => {
+            counts.functions = usize::from(unit.is_unsafe);
+            counts.blocks = count_blocks(|v| v.visit_block(block));
+        }
+        Syntax::Init { expr, .. } => counts.blocks = count_blocks(|v| v.visit_expr(expr)),
+        Syntax::Impl(_) => counts.impls = usize::from(unit.is_unsafe),
+        Syntax::Trait(_) => counts.traits = usize::from(unit.is_unsafe),
+        Syntax::ExternBlock(_) => counts.extern_blocks = 1,
+    }
+    counts
+}
+
+fn count_blocks(visit: impl FnOnce(&mut BlockCounter)) -> usize {
+    let mut counter = BlockCounter(0);
+    visit(&mut counter);
+    counter.0
+}
+
+struct BlockCounter(usize);
+
+impl<'ast> Visit<'ast> for BlockCounter {
+    fn visit_expr_unsafe(&mut self, node: &'ast syn::ExprUnsafe) {
+        self.0 += 1;
+        visit::visit_expr_unsafe(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        for_each_macro_expr(node, |expr| self.visit_expr(expr));
+    }
+}
+
+/// Parses a macro's input as comma-separated expressions, or failing that
+/// as a block body, and calls `f` on each expression found.
+pub fn for_each_macro_expr(mac: &syn::Macro, mut f: impl FnMut(&syn::Expr)) {
+    let parser = Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+    if let Ok(exprs) = mac.parse_body_with(parser) {
+        exprs.iter().for_each(f);
+    } else if let Ok(stmts) = mac.parse_body_with(syn::Block::parse_within) {
+        let block = syn::Block {
+            brace_token: Default::default(),
+            stmts,
+        };
+        f(&syn::Expr::Block(syn::ExprBlock {
+            attrs: Vec::new(),
+            label: None,
+            block,
+        }));
+    }
+}
+
+/// Counts every source file, by item and by module.
+///
+/// Only items with non-zero counts are listed; every module is listed, so
+/// a module that got rid of its unsafe code shows up with zeros.
+pub fn count(files: &[SourceFile]) -> Result<Report, String> {
+    let mut report = Report::default();
+    let mut modules: BTreeMap<String, ModuleReport> = BTreeMap::new();
+
+    for file in files {
+        let syntax = file.parse()?;
+        modules
+            .entry(file.module())
+            .or_insert_with(|| ModuleReport {
+                path: file.module(),
+                file: file.rel_path.clone(),
+                counts: UnsafeCounts::default(),
+            });
+
+        items::walk(&syntax, &file.module_path, &mut |unit| {
+            let counts = count_unit(&unit);
+            // Inline `mod` blocks are modules of their own, in the same file
+            modules
+                .entry(unit.module.clone())
+                .or_insert_with(|| ModuleReport {
+                    path: unit.module.clone(),
+                    file: file.rel_path.clone(),
+                    counts: UnsafeCounts::default(),
+                })
+                .counts += counts;
+            if !counts.is_zero() {
+                report.items.push(ItemReport {
+                    path: unit.path,
+                    kind: unit.kind,
+                    file: file.rel_path.clone(),
+                    line: unit.line,
+                    counts,
+                });
+            }
+        });
+    }
+
+    report.modules = modules.into_values().collect();
+    report.total = report
+        .modules
+        .iter()
+        .fold(UnsafeCounts::default(), |sum, m| sum + m.counts);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts_of(source: &str) -> Vec<(String, UnsafeCounts)> {
+        let file = syn::parse_file(source).unwrap();
+        let mut out = Vec::new();
+        items::walk(&file, &["krate".to_string()], &mut |unit| {
+            let counts = count_unit(&unit);
+            if !counts.is_zero() {
+                out.push((unit.path, counts));
+            }
+        });
+        out
+    }
+
+    #[test]
+    fn test_ignores_comments_and_strings() {
+        let found = counts_of(
+            r#"
+            // unsafe { not code }
+            /// ```
+            /// unsafe { doc example }
+            /// ```
+            fn f() -> &'static str { "unsafe { string }" }
+            "#,
+        );
+        assert!(found.is_empty(), "{:?}", found);
+    }
+
+    #[test]
+    fn test_counts_each_construct() {
+        let found = counts_of(
+            r#"
+            unsafe fn raw(p: *mut i32) -> i32 { *p }
+            fn wrapper(p: *mut i32) -> i32 {
+                let a = unsafe { raw(p) };
+                println!("{}", unsafe { *p });
+                a + unsafe { unsafe { *p } }
+            }
+            unsafe impl Send for Buf {}
+            unsafe trait Zeroable {}
+            extern "C" { fn abs(x: i32) -> i32; }
+            impl Buf { unsafe fn get_unchecked(&self) {} }
+            "#,
+        );
+        let get = |path: &str| found.iter().find(|(p, _)| p == path).unwrap().1;
+
+        assert_eq!(get("krate::raw").functions, 1);
+        assert_eq!(get("krate::raw").blocks, 0);
+        assert_eq!(get("krate::wrapper").blocks, 4);
+        assert_eq!(get("krate::<Buf as Send>").impls, 1);
+        assert_eq!(get("krate::Zeroable").traits, 1);
+        assert_eq!(get("krate::extern \"C\"").extern_blocks, 1);
+        assert_eq!(get("krate::Buf::get_unchecked").functions, 1);
+        assert_eq!(found.len(), 6);
+    }
+}
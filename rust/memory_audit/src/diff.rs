@@ -0,0 +1,133 @@
+//! Unified diffs of rewritten files.
+
+/// Lines of context around each change.
+const CONTEXT: usize = 3;
+
+/// Renders the change from `old` to `new` as a unified diff of `path`, or
+/// an empty string when they are equal.
+pub fn unified(path: &str, old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = edit_script(&a, &b);
+    if ops.iter().all(|op| matches!(op, Op::Same(..))) {
+        return String::new();
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let changed: Vec<usize> = (0..ops.len())
+        .filter(|&i| !matches!(ops[i], Op::Same(..)))
+        .collect();
+
+    // Group changes whose context overlaps into hunks
+    let mut start = 0;
+    while start < changed.len() {
+        let mut end = start;
+        while end + 1 < changed.len() && changed[end + 1] - changed[end] <= 2 * CONTEXT {
+            end += 1;
+        }
+        let from = changed[start].saturating_sub(CONTEXT);
+        let to = (changed[end] + CONTEXT + 1).min(ops.len());
+        hunk(&ops[from..to], &a, &b, &mut out);
+        start = end + 1;
+    }
+    out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    /// Line `.0` of old equals line `.1` of new
+    Same(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Longest-common-subsequence edit script; files here are small.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let (n, m) = (a.len(), b.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            ops.push(Op::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Delete(i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+fn hunk(ops: &[Op], a: &[&str], b: &[&str], out: &mut String) {
+    // Where the hunk starts in each file, 1-based as diff prints it
+    let (mut old_start, mut new_start) = (None, None);
+    let (mut old_len, mut new_len) = (0, 0);
+    let mut body = String::new();
+    let mut old_next = 0;
+    let mut new_next = 0;
+    for op in ops {
+        match *op {
+            Op::Same(i, j) => {
+                old_start.get_or_insert(i + 1);
+                new_start.get_or_insert(j + 1);
+                old_len += 1;
+                new_len += 1;
+                (old_next, new_next) = (i + 1, j + 1);
+                body.push_str(&format!(" {}\n", a[i]));
+            }
+            Op::Delete(i) => {
+                old_start.get_or_insert(i + 1);
+                old_len += 1;
+                old_next = i + 1;
+                body.push_str(&format!("-{}\n", a[i]));
+            }
+            Op::Insert(j) => {
+                new_start.get_or_insert(j + 1);
+                new_len += 1;
+                new_next = j + 1;
+                body.push_str(&format!("+{}\n", b[j]));
+            }
+        }
+    }
+    // An empty side starts after the last line before the hunk
+    let old_start = old_start.unwrap_or(old_next);
+    let new_start = new_start.unwrap_or(new_next);
+    out.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        old_start, old_len, new_start, new_len
+    ));
+    out.push_str(&body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let new = "a\nb\nc\nd\nX\ne\nf\ng\nh\ni\nj\n";
+        assert_eq!(
+            unified("f.rs", old, new),
+            "--- a/f.rs\n+++ b/f.rs\n\
+             @@ -2,6 +2,7 @@\n b\n c\n d\n+X\n e\n f\n g\n\
+             @@ -8,4 +9,3 @@\n h\n i\n j\n-k\n"
+        );
+        assert_eq!(unified("f.rs", old, old), "");
+    }
+}
@@ -0,0 +1,240 @@
+//! Reading sections out of ELF files and the `ar` archives rlibs are.
+//!
+//! Only what finding a named section needs: the ELF header, the section
+//! header table and its string table, for 32- and 64-bit files of either
+//! byte order. Archive members that are not ELF (rlib metadata) are
+//! skipped.
+
+/// The contents of every section called `name` in `data`, which is an ELF
+/// file or an archive of them. Archives can hold several.
+pub fn find_sections<'a>(data: &'a [u8], name: &str) -> Result<Vec<&'a [u8]>, String> {
+    if data.starts_with(b"!<arch>\n") {
+        let mut found = Vec::new();
+        for member in archive_members(data)? {
+            if member.starts_with(ELF_MAGIC) {
+                found.extend(section(member, name)?);
+            }
+        }
+        Ok(found)
+    } else if data.starts_with(ELF_MAGIC) {
+        Ok(section(data, name)?.into_iter().collect())
+    } else {
+        Err("not an ELF file or archive".to_string())
+    }
+}
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// Reads integers in the file's byte order and width.
+struct Reader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    little_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, at: usize, len: usize) -> Result<&'a [u8], String> {
+        at.checked_add(len)
+            .and_then(|end| self.data.get(at..end))
+            .ok_or_else(|| format!("truncated ELF file at offset {:#x}", at))
+    }
+
+    fn uint(&self, at: usize, len: usize) -> Result<u64, String> {
+        let bytes = self.bytes(at, len)?;
+        let fold = |acc: u64, b: &u8| (acc << 8) | u64::from(*b);
+        Ok(if self.little_endian {
+            bytes.iter().rev().fold(0, fold)
+        } else {
+            bytes.iter().fold(0, fold)
+        })
+    }
+
+    fn u16(&self, at: usize) -> Result<usize, String> {
+        self.uint(at, 2).map(|v| v as usize)
+    }
+
+    fn u32(&self, at: usize) -> Result<usize, String> {
+        self.uint(at, 4).map(|v| v as usize)
+    }
+
+    /// An address or offset: 4 bytes in ELF32, 8 in ELF64.
+    fn word(&self, at: usize) -> Result<usize, String> {
+        let len = if self.is_64 { 8 } else { 4 };
+        usize::try_from(self.uint(at, len)?).map_err(|e| e.to_string())
+    }
+}
+
+/// The section called `name` in one ELF file.
+fn section<'a>(data: &'a [u8], name: &str) -> Result<Option<&'a [u8]>, String> {
+    let class = *data.get(4).ok_or("truncated ELF header")?;
+    let encoding = *data.get(5).ok_or("truncated ELF header")?;
+    let elf = Reader {
+        data,
+        is_64: match class {
+            1 => false,
+            2 => true,
+            _ => return Err(format!("unknown ELF class {}", class)),
+        },
+        little_endian: match encoding {
+            1 => true,
+            2 => false,
+            _ => return Err(format!("unknown ELF data encoding {}", encoding)),
+        },
+    };
+
+    // Field offsets of the ELF header, then of a section header
+    let (shoff, shentsize, shnum, shstrndx) = if elf.is_64 {
+        (0x28, 0x3a, 0x3c, 0x3e)
+    } else {
+        (0x20, 0x2e, 0x30, 0x32)
+    };
+    let (sh_offset, sh_size) = if elf.is_64 {
+        (0x18, 0x20)
+    } else {
+        (0x10, 0x14)
+    };
+    let table = elf.word(shoff)?;
+    let entry_size = elf.u16(shentsize)?;
+    let mut count = elf.u16(shnum)?;
+    let mut names_index = elf.u16(shstrndx)?;
+    if table == 0 {
+        return Ok(None);
+    }
+    // Extended numbering: the real values live in section header 0
+    if count == 0 {
+        count = elf.word(table + if elf.is_64 { 0x20 } else { 0x14 })?;
+    }
+    if names_index == 0xffff {
+        names_index = elf.u32(table + if elf.is_64 { 0x2c } else { 0x18 })?;
+    }
+
+    let header = |index: usize| table + index * entry_size;
+    let contents = |index: usize| -> Result<&'a [u8], String> {
+        let offset = elf.word(header(index) + sh_offset)?;
+        let size = elf.word(header(index) + sh_size)?;
+        elf.bytes(offset, size)
+    };
+    let names = contents(names_index)?;
+    for index in 0..count {
+        let name_at = elf.u32(header(index))?;
+        let section_name = names
+            .get(name_at..)
+            .and_then(|rest| rest.split(|&b| b == 0).next())
+            .ok_or("section name out of bounds")?;
+        if section_name == name.as_bytes() {
+            // SHT_NOBITS sections occupy no file space
+            let kind = elf.u32(header(index) + 4)?;
+            return Ok(Some(if kind == 8 { &[] } else { contents(index)? }));
+        }
+    }
+    Ok(None)
+}
+
+/// The members of an `ar` archive, with GNU and BSD name conventions.
+fn archive_members(data: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let mut members = Vec::new();
+    let mut at = 8;
+    while at + 60 <= data.len() {
+        let header = &data[at..at + 60];
+        let field = |range: std::ops::Range<usize>| {
+            String::from_utf8_lossy(&header[range]).trim().to_string()
+        };
+        let name = field(0..16);
+        let size: usize = field(48..58)
+            .parse()
+            .map_err(|_| format!("bad archive member size at offset {:#x}", at))?;
+        let start = at + 60;
+        let body = data
+            .get(start..start + size)
+            .ok_or("truncated archive member")?;
+        // BSD long names: `#1/<len>`, the name prefixed to the data
+        let body = match name.strip_prefix("#1/").map(str::parse::<usize>) {
+            Some(Ok(name_len)) => body.get(name_len..).unwrap_or_default(),
+            _ => body,
+        };
+        // Skip the symbol table and the GNU long-name table
+        if !matches!(
+            name.as_str(),
+            "/" | "//" | "/SYM64/" | "__.SYMDEF" | "__.SYMDEF SORTED"
+        ) {
+            members.push(body);
+        }
+        // Members are 2-byte aligned
+        at = start + size + size % 2;
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal little-endian ELF64 file: a null section, `.msafety`
+    /// holding `contents`, and the section name table.
+    fn elf64(contents: &[u8]) -> Vec<u8> {
+        let names = b"\0.msafety\0.shstrtab\0";
+        let mut data = vec![0u8; 64];
+        data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        let contents_at = data.len();
+        data.extend_from_slice(contents);
+        let names_at = data.len();
+        data.extend_from_slice(names);
+        let table = data.len();
+
+        let mut header = |name: u32, kind: u32, offset: usize, size: usize| {
+            let mut sh = vec![0u8; 64];
+            sh[0..4].copy_from_slice(&name.to_le_bytes());
+            sh[4..8].copy_from_slice(&kind.to_le_bytes());
+            sh[0x18..0x20].copy_from_slice(&(offset as u64).to_le_bytes());
+            sh[0x20..0x28].copy_from_slice(&(size as u64).to_le_bytes());
+            data.extend_from_slice(&sh);
+        };
+        header(0, 0, 0, 0);
+        header(1, 1, contents_at, contents.len());
+        header(10, 3, names_at, names.len());
+
+        data[0x28..0x30].copy_from_slice(&(table as u64).to_le_bytes());
+        data[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        data[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+        data[0x3e..0x40].copy_from_slice(&2u16.to_le_bytes());
+        data
+    }
+
+    fn ar_member(name: &str, body: &[u8]) -> Vec<u8> {
+        let mut member = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            644,
+            body.len()
+        )
+        .into_bytes();
+        member.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    #[test]
+    fn test_finds_section_in_elf_and_archive() {
+        let object = elf64(b"MSA1 summary");
+        assert_eq!(
+            find_sections(&object, ".msafety").unwrap(),
+            [b"MSA1 summary"]
+        );
+        assert!(find_sections(&object, ".text").unwrap().is_empty());
+
+        let mut archive = b"!<arch>\n".to_vec();
+        archive.extend(ar_member("lib.rmeta/", b"not elf"));
+        archive.extend(ar_member("a.o/", &object));
+        assert_eq!(
+            find_sections(&archive, ".msafety").unwrap(),
+            [b"MSA1 summary"]
+        );
+
+        assert!(find_sections(b"MZ", ".msafety").is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Safety summaries embedded in compiled artifacts.
+//!
+//! A crate's build script calls [`build_script`], which audits the crate's
+//! `src/` and writes a static holding the summary into `OUT_DIR`; the crate
+//! `include!`s it. Embedding is opt-in: `memory_lib` and `memory_app` only
+//! depend on this crate at build time with their `safety-summary` feature,
+//! so their ordinary builds don't compile syn and friends. The static lives
+//! in its own linker section, so the summary ships inside the rlib and every
+//! binary linked from it, where [`crate::elf`] can read it back without the
+//! sources. Mach-O names sections `segment,section`, so Apple targets use
+//! [`MACHO_SECTION`].
+//!
+//! Each summary is one record: `MSA1`, a little-endian `u16` payload length,
+//! then `key=value` lines. The linker concatenates the records of every
+//! crate in a binary into one section, so a binary built from `memory_app`
+//! carries `memory_lib`'s record too.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::count::{self, UnsafeCounts};
+use crate::geiger::{self, Cfg};
+use crate::hash::hash;
+use crate::items;
+use crate::ledger;
+use crate::policy;
+use crate::workspace;
+
+/// Section name; short enough for PE, and valid for ELF.
+pub const SECTION: &str = ".msafety";
+
+/// Segment and section name on Mach-O, which rejects [`SECTION`].
+pub const MACHO_SECTION: &str = "__DATA,__msafety";
+
+/// Start of every record.
+pub const MAGIC: &[u8; 4] = b"MSA1";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub crate_name: String,
+    pub version: String,
+    /// Level of the `unsafe_code` lint (`forbid`, `deny`, `warn`, `allow`)
+    /// the workspace's `safety-policy.toml` requires of the crate, else the
+    /// one its crate root declares, or `none` when neither says
+    pub policy: String,
+    pub counts: UnsafeCounts,
+    /// Hash of the package's `safety-ledger.toml`, if it keeps one
+    pub ledger: Option<String>,
+}
+
+/// Audits the package in `dir` as it is compiled for `cfg`: the module
+/// trees of `src/lib.rs` and `src/main.rs`, without the items `cfg`
+/// disables, so tests, benches, examples and `#[cfg(test)]` modules are
+/// not counted.
+pub fn summarize(dir: &Path, cfg: &Cfg) -> Result<Summary, String> {
+    let package = workspace::packages(dir)?
+        .into_iter()
+        .find(|p| p.dir == dir)
+        .ok_or_else(|| format!("{}: not a package", dir.display()))?;
+    let roots: Vec<PathBuf> = ["lib.rs", "main.rs"]
+        .iter()
+        .map(|root| dir.join("src").join(root))
+        .filter(|root| root.exists())
+        .collect();
+    let trees = geiger::module_trees(&roots, cfg)?;
+    let module = [package.crate_name()];
+    let mut counts = UnsafeCounts::default();
+    for syntax in &trees {
+        items::walk(syntax, &module, &mut |unit| {
+            counts += count::count_unit(&unit)
+        });
+    }
+
+    let required = match policy_file(dir) {
+        Some(path) => {
+            let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            let parsed =
+                policy::Policy::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            parsed.crates.get(&package.name).map(|c| c.unsafe_code)
+        }
+        None => None,
+    };
+    let declared = trees
+        .first()
+        .and_then(|root| policy::declared_level(&root.attrs))
+        .map(|(level, _)| level);
+    let policy = required
+        .or(declared)
+        .map_or("none", |level| level.as_str())
+        .to_string();
+
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).map_err(|e| e.to_string())?;
+    let version = manifest
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|t| {
+            t.get("package")?
+                .get("version")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+    let ledger = fs::read_to_string(dir.join(ledger::FILE_NAME))
+        .ok()
+        .map(|text| hash(&text));
+
+    Ok(Summary {
+        crate_name: package.crate_name(),
+        version,
+        policy,
+        counts,
+        ledger,
+    })
+}
+
+impl Summary {
+    /// The summary as one record.
+    pub fn encode(&self) -> Vec<u8> {
+        let c = &self.counts;
+        let payload = format!(
+            "crate={}\nversion={}\npolicy={}\nblocks={}\nfunctions={}\nimpls={}\ntraits={}\nextern_blocks={}\nledger={}\n",
+            self.crate_name,
+            self.version,
+            self.policy,
+            c.blocks,
+            c.functions,
+            c.impls,
+            c.traits,
+            c.extern_blocks,
+            self.ledger.as_deref().unwrap_or("none"),
+        );
+        let len = u16::try_from(payload.len()).expect("summary fits in a record");
+        let mut record = MAGIC.to_vec();
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(payload.as_bytes());
+        record
+    }
+
+    fn decode_payload(payload: &str) -> Summary {
+        let mut summary = Summary::default();
+        for (key, value) in payload.lines().filter_map(|l| l.split_once('=')) {
+            let number = || value.parse().unwrap_or(0);
+            match key {
+                "crate" => summary.crate_name = value.to_string(),
+                "version" => summary.version = value.to_string(),
+                "policy" => summary.policy = value.to_string(),
+                "blocks" => summary.counts.blocks = number(),
+                "functions" => summary.counts.functions = number(),
+                "impls" => summary.counts.impls = number(),
+                "traits" => summary.counts.traits = number(),
+                "extern_blocks" => summary.counts.extern_blocks = number(),
+                "ledger" if value != "none" => summary.ledger = Some(value.to_string()),
+                // Keys from newer versions
+                _ => {}
+            }
+        }
+        summary
+    }
+}
+
+/// Reads every record in a section's contents. Bytes between records, such
+/// as alignment padding, are skipped.
+pub fn decode_all(section: &[u8]) -> Vec<Summary> {
+    let mut summaries = Vec::new();
+    let mut at = 0;
+    while let Some(found) = find(&section[at..], MAGIC) {
+        let start = at + found + MAGIC.len();
+        let Some(len) = section.get(start..start + 2) else {
+            break;
+        };
+        let len = usize::from(u16::from_le_bytes([len[0], len[1]]));
+        let Some(payload) = section.get(start + 2..start + 2 + len) else {
+            break;
+        };
+        summaries.push(Summary::decode_payload(&String::from_utf8_lossy(payload)));
+        at = start + 2 + len;
+    }
+    summaries
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Rust source of a static holding `summary` in [`SECTION`], or
+/// [`MACHO_SECTION`] on Apple targets.
+pub fn static_source(summary: &Summary) -> String {
+    let record = summary.encode();
+    let bytes: Vec<String> = record.iter().map(|b| format!("{:#04x}", b)).collect();
+    format!(
+        "// Generated by memory_audit::embed; do not edit.\n\
+         #[used]\n\
+         #[cfg_attr(not(target_vendor = \"apple\"), link_section = \"{}\")]\n\
+         #[cfg_attr(target_vendor = \"apple\", link_section = \"{}\")]\n\
+         static MEMORY_SAFETY_SUMMARY: [u8; {}] = [{}];\n",
+        SECTION,
+        MACHO_SECTION,
+        record.len(),
+        bytes.join(", ")
+    )
+}
+
+/// Entry point for a crate's `build.rs`: writes `safety_summary.rs` to
+/// `OUT_DIR` for the crate to `include!`.
+pub fn build_script() {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").expect("run by cargo");
+    let out = std::env::var("OUT_DIR").expect("run by cargo");
+    let dir = Path::new(&dir);
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed={}", ledger::FILE_NAME);
+    if let Some(path) = policy_file(dir) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+
+    let features = enabled_features(dir);
+    let cfg =
+        Cfg::from_cargo_env(std::env::vars()).with_features(features.iter().map(String::as_str));
+    let summary = summarize(dir, &cfg).unwrap_or_else(|e| panic!("memory_audit: {}", e));
+    fs::write(
+        Path::new(&out).join("safety_summary.rs"),
+        static_source(&summary),
+    )
+    .expect("OUT_DIR is writable");
+}
+
+/// The `safety-policy.toml` of the workspace `dir` is in: the nearest one
+/// in `dir` or above it.
+fn policy_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(policy::FILE_NAME))
+        .find(|path| path.exists())
+}
+
+/// The features of the package in `dir` that cargo enabled for the build
+/// script. `CARGO_FEATURE_*` names are upper-cased with `-` as `_`, so they
+/// are matched against the features the manifest declares.
+fn enabled_features(dir: &Path) -> Vec<String> {
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).unwrap_or_default();
+    let Ok(manifest) = manifest.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let declared = manifest.get("features").and_then(|f| f.as_table());
+    declared
+        .into_iter()
+        .flat_map(|features| features.keys())
+        .filter(|name| {
+            let var = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+            std::env::var_os(var).is_some()
+        })
+        .cloned()
+        .collect()
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = &self.counts;
+        writeln!(f, "{} {}", self.crate_name, self.version)?;
+        writeln!(f, "  unsafe_code policy: {}", self.policy)?;
+        writeln!(
+            f,
+            "  unsafe blocks {}, fns {}, impls {}, traits {}, extern blocks {}",
+            c.blocks, c.functions, c.impls, c.traits, c.extern_blocks
+        )?;
+        writeln!(
+            f,
+            "  safety ledger: {}",
+            self.ledger.as_deref().unwrap_or("none")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_round_trip() {
+        let lib = Summary {
+            crate_name: "memory_lib".to_string(),
+            version: "0.1.0".to_string(),
+            policy: "none".to_string(),
+            counts: UnsafeCounts {
+                blocks: 40,
+                functions: 15,
+                impls: 7,
+                ..UnsafeCounts::default()
+            },
+            ledger: Some("0123456789abcdef".to_string()),
+        };
+        let app = Summary {
+            crate_name: "memory_app".to_string(),
+            policy: "forbid".to_string(),
+            ..Summary::default()
+        };
+        // As the linker lays them out: concatenated, maybe padded
+        let mut section = lib.encode();
+        section.extend_from_slice(&[0; 3]);
+        section.extend_from_slice(&app.encode());
+        assert_eq!(decode_all(&section), [lib, app]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Findings reported by the audit checks.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// The rule a finding breaks. Ids are stable and never reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    /// An `unsafe { }` block without a `// SAFETY DISCHARGE:` comment
+    MissingDischarge,
+    /// An `unsafe fn` without a `# Safety` doc section
+    MissingSafetyDoc,
+    /// An `unsafe impl` without a `// SAFETY:` comment
+    MissingImplRationale,
+    /// A safe public fn that lets a raw pointer cross its signature
+    LeakySuppression,
+    /// Unsafe code that changed since its safety ledger entry was written
+    StaleReview,
+    /// Unsafe code with no safety ledger entry
+    UnreviewedUnsafe,
+    /// A crate root whose `unsafe_code` level differs from the policy's
+    PolicyAttribute,
+    /// An `unsafe fn` outside the modules the policy lets define them
+    PolicyUnsafeFn,
+    /// Unsafe code where the policy allows none
+    PolicyUnsafeCode,
+    /// A package missing from the policy, or a policy entry naming nothing
+    PolicyEntry,
+    /// Unsafe counts above the package's recorded baseline
+    UnsafeGrowth,
+}
+
+impl Rule {
+    /// Every rule, in declaration order.
+    pub const ALL: [Rule; 11] = [
+        Rule::MissingDischarge,
+        Rule::MissingSafetyDoc,
+        Rule::MissingImplRationale,
+        Rule::LeakySuppression,
+        Rule::StaleReview,
+        Rule::UnreviewedUnsafe,
+        Rule::PolicyAttribute,
+        Rule::PolicyUnsafeFn,
+        Rule::PolicyUnsafeCode,
+        Rule::PolicyEntry,
+        Rule::UnsafeGrowth,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::MissingDischarge => "missing-discharge",
+            Rule::MissingSafetyDoc => "missing-safety-doc",
+            Rule::MissingImplRationale => "missing-impl-rationale",
+            Rule::LeakySuppression => "leaky-suppression",
+            Rule::StaleReview => "stale-review",
+            Rule::UnreviewedUnsafe => "unreviewed-unsafe",
+            Rule::PolicyAttribute => "policy-attribute",
+            Rule::PolicyUnsafeFn => "policy-unsafe-fn",
+            Rule::PolicyUnsafeCode => "policy-unsafe-code",
+            Rule::PolicyEntry => "policy-entry",
+            Rule::UnsafeGrowth => "unsafe-growth",
+        }
+    }
+
+    /// One sentence saying what the rule requires.
+    pub fn description(self) -> &'static str {
+        match self {
+            Rule::MissingDischarge => {
+                "Every unsafe block carries a `// SAFETY DISCHARGE:` comment."
+            }
+            Rule::MissingSafetyDoc => "Every unsafe fn documents its contract under `# Safety`.",
+            Rule::MissingImplRationale => "Every unsafe impl carries a `// SAFETY:` comment.",
+            Rule::LeakySuppression => {
+                "Raw pointers do not cross the signature of a safe public fn."
+            }
+            Rule::StaleReview => "Reviewed unsafe code still matches its safety ledger entry.",
+            Rule::UnreviewedUnsafe => "Every unsafe block and impl has a safety ledger entry.",
+            Rule::PolicyAttribute => {
+                "Crate roots declare the `unsafe_code` level the safety policy requires."
+            }
+            Rule::PolicyUnsafeFn => "Unsafe fns are defined only where the safety policy allows.",
+            Rule::PolicyUnsafeCode => {
+                "Crates and modules the safety policy marks safe contain no unsafe code."
+            }
+            Rule::PolicyEntry => "The safety policy covers every package and names real ones.",
+            Rule::UnsafeGrowth => "Unsafe counts never rise above the package's baseline.",
+        }
+    }
+
+    /// How to fix a finding.
+    pub fn help(self) -> &'static str {
+        match self {
+            Rule::MissingDischarge => {
+                "Explain why the block is sound in a `// SAFETY DISCHARGE:` comment directly \
+                 above its statement, or turn the enclosing fn into an `unsafe fn` that \
+                 propagates the obligation to its callers."
+            }
+            Rule::MissingSafetyDoc => {
+                "Add a `# Safety` section to the doc comment listing what callers must \
+                 guarantee."
+            }
+            Rule::MissingImplRationale => {
+                "Explain why the type upholds the trait's contract in a `// SAFETY:` comment \
+                 directly above the impl."
+            }
+            Rule::LeakySuppression => {
+                "Make the fn `unsafe` so callers take on the pointer's obligations, or wrap \
+                 the pointer in an owning type whose safe API releases it."
+            }
+            Rule::StaleReview => {
+                "Review the changed code, then run `memory_audit ledger --record REVIEWER \
+                 --item PATH` to record the review."
+            }
+            Rule::UnreviewedUnsafe => {
+                "Review the code, then run `memory_audit ledger --record REVIEWER --item \
+                 PATH` to add its entry to the package's `safety-ledger.toml`."
+            }
+            Rule::PolicyAttribute => {
+                "Declare the level from `safety-policy.toml` at the crate root, or change \
+                 the policy."
+            }
+            Rule::PolicyUnsafeFn => {
+                "Move the unsafe fn into a module listed in the crate's `unsafe_fn`, make \
+                 it safe, or add its module to `unsafe_fn` in `safety-policy.toml`."
+            }
+            Rule::PolicyUnsafeCode => {
+                "Move the unsafe code into a module that may contain it, or change \
+                 `safety-policy.toml`."
+            }
+            Rule::PolicyEntry => {
+                "Add a `[crates.NAME]` entry for every workspace package to \
+                 `safety-policy.toml`, and remove names that no longer exist."
+            }
+            Rule::UnsafeGrowth => {
+                "Remove the new unsafe code, or once the growth is reviewed and intended, \
+                 record it with `memory_audit baseline --update-baseline`."
+            }
+        }
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+/// One problem at one place in the source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub rule: Rule,
+    /// Path relative to the workspace root
+    pub file: String,
+    pub line: usize,
+    /// Path of the item the finding is in
+    pub item: String,
+    pub message: String,
+    /// Another item the finding refers to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub related: Option<Related>,
+}
+
+/// An item a finding points to, such as the free function for a pointer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Related {
+    pub item: String,
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {} (in {})",
+            self.file,
+            self.line,
+            self.rule.id(),
+            self.message,
+            self.item
+        )?;
+        if let Some(related) = &self.related {
+            write!(
+                f,
+                "\n    see {} ({}:{})",
+                related.item, related.file, related.line
+            )?;
+        }
+        Ok(())
+    }
+}
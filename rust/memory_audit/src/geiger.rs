@@ -0,0 +1,805 @@
+//! Unsafe metrics for the whole dependency tree, offline.
+//!
+//! A stand-in for `cargo geiger` on machines that can't install it. The
+//! dependency graph comes from `cargo metadata --offline` (with `--locked`
+//! when there is a `Cargo.lock`, so the versions are exactly the locked
+//! ones), and sources are read wherever cargo keeps them: the registry
+//! cache, a vendor directory, a git checkout or the workspace itself.
+//!
+//! Metrics follow geiger's `x/y` format:
+//!
+//! - `y` counts every `.rs` file in the package
+//! - `x` counts only the code the build uses: the module tree of the lib
+//!   and bin targets, following `mod` declarations, with items whose
+//!   `#[cfg]` is false for the host and the resolved features removed
+//!
+//! As in geiger, expressions are counted one by one inside `unsafe { }`
+//! blocks and `unsafe fn` bodies, and free functions are told apart from
+//! methods. Modules declared by macros are not followed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::ops::{Add, AddAssign};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Serialize;
+use syn::visit::{self, Visit};
+use syn::{Attribute, ImplItem, Item, Meta, TraitItem};
+
+use crate::count::for_each_macro_expr;
+use crate::items::{self, ItemKind, Syntax};
+use crate::policy::{self, Level};
+
+/// Unsafe code in geiger's categories.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GeigerCounts {
+    /// Free `unsafe fn`s
+    pub functions: usize,
+    /// Expressions inside `unsafe { }` blocks and `unsafe fn` bodies
+    pub expressions: usize,
+    pub impls: usize,
+    pub traits: usize,
+    /// `unsafe fn` methods, in impls and traits
+    pub methods: usize,
+}
+
+impl GeigerCounts {
+    pub fn is_zero(&self) -> bool {
+        *self == GeigerCounts::default()
+    }
+}
+
+impl Add for GeigerCounts {
+    type Output = GeigerCounts;
+
+    fn add(self, other: GeigerCounts) -> GeigerCounts {
+        GeigerCounts {
+            functions: self.functions + other.functions,
+            expressions: self.expressions + other.expressions,
+            impls: self.impls + other.impls,
+            traits: self.traits + other.traits,
+            methods: self.methods + other.methods,
+        }
+    }
+}
+
+impl AddAssign for GeigerCounts {
+    fn add_assign(&mut self, other: GeigerCounts) {
+        *self = *self + other;
+    }
+}
+
+/// One package of the dependency graph.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PackageReport {
+    pub name: String,
+    pub version: String,
+    /// Where cargo got it from; `None` for path dependencies
+    pub source: Option<String>,
+    /// Whether its crate roots carry `#![forbid(unsafe_code)]`
+    pub forbids_unsafe: bool,
+    pub used: GeigerCounts,
+    pub total: GeigerCounts,
+    /// Files that could not be read or parsed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Names and versions of its normal and build dependencies
+    pub dependencies: Vec<String>,
+}
+
+impl PackageReport {
+    pub fn key(&self) -> String {
+        format!("{} {}", self.name, self.version)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GeigerReport {
+    /// Keys of the workspace members, the roots of the tree
+    pub roots: Vec<String>,
+    /// Every package, sorted by key
+    pub packages: Vec<PackageReport>,
+}
+
+/// The `cfg` settings the build uses: the host's, plus enabled features.
+#[derive(Clone, Debug, Default)]
+pub struct Cfg {
+    /// `unix` is `("unix", None)`, `target_os="linux"` is
+    /// `("target_os", Some("linux"))`
+    set: BTreeSet<(String, Option<String>)>,
+}
+
+impl Cfg {
+    /// The host's settings, as `rustc --print cfg` reports them.
+    pub fn host() -> Result<Cfg, String> {
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--print", "cfg"])
+            .output()
+            .map_err(|e| format!("failed to run rustc: {}", e))?;
+        Ok(Cfg::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// The settings of the target a build script is building for, from the
+    /// `CARGO_CFG_*` variables cargo passes it. Multi-valued settings such
+    /// as `target_feature` are comma-separated there.
+    pub fn from_cargo_env(vars: impl IntoIterator<Item = (String, String)>) -> Cfg {
+        let mut set = BTreeSet::new();
+        for (var, value) in vars {
+            let Some(key) = var.strip_prefix("CARGO_CFG_") else {
+                continue;
+            };
+            let key = key.to_ascii_lowercase();
+            if value.is_empty() {
+                set.insert((key, None));
+            } else {
+                for value in value.split(',') {
+                    set.insert((key.clone(), Some(value.to_string())));
+                }
+            }
+        }
+        Cfg { set }
+    }
+
+    pub fn parse(print_cfg: &str) -> Cfg {
+        let set = print_cfg
+            .lines()
+            .map(|line| match line.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.trim_matches('"').to_string())),
+                None => (line.to_string(), None),
+            })
+            .collect();
+        Cfg { set }
+    }
+
+    pub fn with_features<'a>(&self, features: impl IntoIterator<Item = &'a str>) -> Cfg {
+        let mut cfg = self.clone();
+        for feature in features {
+            cfg.set
+                .insert(("feature".to_string(), Some(feature.to_string())));
+        }
+        cfg
+    }
+
+    /// Whether the `#[cfg]` attributes in `attrs` all hold.
+    pub fn enabled(&self, attrs: &[Attribute]) -> bool {
+        attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .all(|attr| match &attr.meta {
+                Meta::List(list) => list
+                    .parse_args::<Meta>()
+                    .map_or(true, |predicate| self.eval(&predicate)),
+                _ => true,
+            })
+    }
+
+    fn eval(&self, predicate: &Meta) -> bool {
+        let name = items::path_name(predicate.path());
+        match predicate {
+            Meta::Path(_) => self.set.contains(&(name, None)),
+            Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(value),
+                    ..
+                }) => self.set.contains(&(name, Some(value.value()))),
+                _ => false,
+            },
+            Meta::List(list) => {
+                let parser = syn::punctuated::Punctuated::<Meta, syn::Token![,]>::parse_terminated;
+                let Ok(args) = list.parse_args_with(parser) else {
+                    return true;
+                };
+                match name.as_str() {
+                    "all" => args.iter().all(|a| self.eval(a)),
+                    "any" => args.iter().any(|a| self.eval(a)),
+                    "not" => !args.iter().all(|a| self.eval(a)),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// Builds the report for the workspace at `root`.
+pub fn geiger(root: &Path) -> Result<GeigerReport, String> {
+    let metadata = metadata(root)?;
+    let host = Cfg::host()?;
+
+    let packages: BTreeMap<&str, &serde_json::Value> = metadata["packages"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|p| Some((p["id"].as_str()?, p)))
+        .collect();
+    let key = |id: &str| {
+        let package = packages[id];
+        format!(
+            "{} {}",
+            package["name"].as_str().unwrap_or_default(),
+            package["version"].as_str().unwrap_or_default()
+        )
+    };
+
+    let mut report = GeigerReport::default();
+    let nodes = metadata["resolve"]["nodes"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for node in nodes {
+        let id = node["id"].as_str().unwrap_or_default();
+        let Some(package) = packages.get(id) else {
+            continue;
+        };
+        let features: Vec<&str> = node["features"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|f| f.as_str())
+            .collect();
+        let mut dependencies: Vec<String> = node["deps"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|dep| {
+                dep["dep_kinds"].as_array().is_some_and(|kinds| {
+                    kinds
+                        .iter()
+                        .any(|k| k["kind"].is_null() || k["kind"] == "build")
+                })
+            })
+            .filter_map(|dep| dep["pkg"].as_str())
+            .filter(|id| packages.contains_key(id))
+            .map(key)
+            .collect();
+        dependencies.sort();
+        dependencies.dedup();
+        report.packages.push(measure(
+            package,
+            &host.with_features(features),
+            dependencies,
+        ));
+    }
+    report.packages.sort_by_key(PackageReport::key);
+
+    report.roots = metadata["workspace_members"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|id| id.as_str())
+        .filter(|id| packages.contains_key(id))
+        .map(key)
+        .collect();
+    report.roots.sort();
+    Ok(report)
+}
+
+/// Runs `cargo metadata` for the host platform without touching the
+/// network.
+fn metadata(root: &Path) -> Result<serde_json::Value, String> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("-vV")
+        .output()
+        .map_err(|e| format!("failed to run rustc: {}", e))?;
+    let host = String::from_utf8_lossy(&version.stdout)
+        .lines()
+        .find_map(|l| l.strip_prefix("host: ").map(str::to_string))
+        .ok_or("rustc -vV reported no host")?;
+
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command
+        .args(["metadata", "--format-version", "1", "--offline"])
+        .args(["--filter-platform", &host])
+        .current_dir(root);
+    if root.join("Cargo.lock").exists() {
+        command.arg("--locked");
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("failed to run cargo: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("cargo metadata: {}", e))
+}
+
+/// Measures one package from its `cargo metadata` entry.
+fn measure(package: &serde_json::Value, cfg: &Cfg, dependencies: Vec<String>) -> PackageReport {
+    let text = |key: &str| package[key].as_str().unwrap_or_default().to_string();
+    let manifest = PathBuf::from(text("manifest_path"));
+    let dir = manifest.parent().unwrap_or(Path::new("."));
+    let roots: Vec<PathBuf> = package["targets"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|target| {
+            target["kind"].as_array().is_some_and(|kinds| {
+                kinds.iter().any(|k| {
+                    [
+                        "lib",
+                        "rlib",
+                        "dylib",
+                        "cdylib",
+                        "staticlib",
+                        "proc-macro",
+                        "bin",
+                    ]
+                    .contains(&k.as_str().unwrap_or_default())
+                })
+            })
+        })
+        .filter_map(|target| target["src_path"].as_str().map(PathBuf::from))
+        .collect();
+
+    let mut errors = Vec::new();
+    let mut used = GeigerCounts::default();
+    let mut forbids_unsafe = !roots.is_empty();
+    let mut seen = BTreeSet::new();
+    for root in &roots {
+        match parse(root) {
+            Ok(syntax) => forbids_unsafe &= forbids(&syntax.attrs),
+            Err(e) => errors.push(e),
+        }
+        used += used_counts(root, &root_dir(root), cfg, &mut seen, &mut errors);
+    }
+
+    let mut files = Vec::new();
+    if let Err(e) = rs_files(dir, &mut files) {
+        errors.push(format!("{}: {}", dir.display(), e));
+    }
+    files.sort();
+    let mut total = GeigerCounts::default();
+    for file in &files {
+        match parse(file) {
+            Ok(syntax) => total += counts(&syntax),
+            Err(e) if !errors.contains(&e) => errors.push(e),
+            Err(_) => {}
+        }
+    }
+
+    PackageReport {
+        name: text("name"),
+        version: text("version"),
+        source: package["source"].as_str().map(str::to_string),
+        forbids_unsafe,
+        used,
+        total,
+        errors,
+        dependencies,
+    }
+}
+
+fn parse(path: &Path) -> Result<syn::File, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    syn::parse_file(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn forbids(attrs: &[Attribute]) -> bool {
+    policy::declared_level(attrs).is_some_and(|(level, _)| level == Level::Forbid)
+}
+
+/// Where a crate root's `mod` declarations are looked up: its directory.
+fn root_dir(root: &Path) -> PathBuf {
+    root.parent().unwrap_or(Path::new(".")).to_path_buf()
+}
+
+/// Counts the module tree rooted at `file`, whose child modules live in
+/// `dir`, keeping only what `cfg` enables. `seen` stops files included
+/// twice from counting twice.
+fn used_counts(
+    file: &Path,
+    dir: &Path,
+    cfg: &Cfg,
+    seen: &mut BTreeSet<PathBuf>,
+    errors: &mut Vec<String>,
+) -> GeigerCounts {
+    let mut total = GeigerCounts::default();
+    visit_tree(file, dir, cfg, seen, errors, &mut |syntax| {
+        total += counts(&syntax)
+    });
+    total
+}
+
+/// Parses the module trees of the crate roots `roots`, following `mod`
+/// declarations, with the items and files `cfg` disables removed.
+pub fn module_trees(roots: &[PathBuf], cfg: &Cfg) -> Result<Vec<syn::File>, String> {
+    let mut files = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut errors = Vec::new();
+    for root in roots {
+        visit_tree(
+            root,
+            &root_dir(root),
+            cfg,
+            &mut seen,
+            &mut errors,
+            &mut |syntax| files.push(syntax),
+        );
+    }
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(files),
+    }
+}
+
+/// Calls `f` on every file of the module tree rooted at `file`, stripped
+/// as `used_counts` describes.
+fn visit_tree(
+    file: &Path,
+    dir: &Path,
+    cfg: &Cfg,
+    seen: &mut BTreeSet<PathBuf>,
+    errors: &mut Vec<String>,
+    f: &mut impl FnMut(syn::File),
+) {
+    if !seen.insert(file.to_path_buf()) {
+        return;
+    }
+    let mut syntax = match parse(file) {
+        Ok(syntax) => syntax,
+        Err(e) => {
+            errors.push(e);
+            return;
+        }
+    };
+    if !cfg.enabled(&syntax.attrs) {
+        return;
+    }
+    strip(&mut syntax.items, cfg);
+    let children = child_modules(&syntax.items, dir, &root_dir(file));
+    f(syntax);
+    for (path, child_dir) in children {
+        visit_tree(&path, &child_dir, cfg, seen, errors, f);
+    }
+}
+
+/// Removes the items `cfg` disables, at any depth.
+fn strip(items: &mut Vec<Item>, cfg: &Cfg) {
+    items.retain(|item| cfg.enabled(item_attrs(item)));
+    for item in items {
+        match item {
+            Item::Mod(module) => {
+                if let Some((_, content)) = &mut module.content {
+                    strip(content, cfg);
+                }
+            }
+            Item::Impl(imp) => imp.items.retain(|i| match i {
+                ImplItem::Fn(f) => cfg.enabled(&f.attrs),
+                ImplItem::Const(c) => cfg.enabled(&c.attrs),
+                _ => true,
+            }),
+            Item::Trait(tr) => tr.items.retain(|i| match i {
+                TraitItem::Fn(f) => cfg.enabled(&f.attrs),
+                _ => true,
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Fn(i) => &i.attrs,
+        Item::Impl(i) => &i.attrs,
+        Item::Trait(i) => &i.attrs,
+        Item::Mod(i) => &i.attrs,
+        Item::ForeignMod(i) => &i.attrs,
+        Item::Static(i) => &i.attrs,
+        Item::Const(i) => &i.attrs,
+        Item::Macro(i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+/// Files of the `mod name;` declarations in `items`, with the directory
+/// each one's own children live in. Children are looked up in `dir`, and
+/// `#[path]` is relative to `path_dir`, the directory of the file itself.
+/// Inline modules are entered.
+fn child_modules(items: &[Item], dir: &Path, path_dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let mut out = Vec::new();
+    for item in items {
+        let Item::Mod(module) = item else {
+            continue;
+        };
+        let name = module.ident.to_string();
+        let path_attr = module.attrs.iter().find_map(|attr| match &attr.meta {
+            Meta::NameValue(nv) if nv.path.is_ident("path") => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(path),
+                    ..
+                }) => Some(path.value()),
+                _ => None,
+            },
+            _ => None,
+        });
+        match (&module.content, path_attr) {
+            (Some((_, content)), _) => {
+                let inner = dir.join(&name);
+                out.extend(child_modules(content, &inner, &inner));
+            }
+            (None, Some(path)) => {
+                let file = path_dir.join(path);
+                let child_dir = root_dir(&file);
+                out.push((file, child_dir));
+            }
+            (None, None) => {
+                let flat = dir.join(format!("{}.rs", name));
+                if flat.exists() {
+                    out.push((flat, dir.join(&name)));
+                } else {
+                    out.push((dir.join(&name).join("mod.rs"), dir.join(&name)));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Counts one parsed file.
+fn counts(syntax: &syn::File) -> GeigerCounts {
+    let mut counts = GeigerCounts::default();
+    items::walk(syntax, &[String::new()], &mut |unit| match unit.syntax {
+        Syntax::Fn { block, .. } => {
+            if unit.is_unsafe {
+                match unit.kind {
+                    ItemKind::Function => counts.functions += 1,
+                    _ => counts.methods += 1,
+                }
+            }
+            let mut counter = ExprCounter {
+                in_unsafe: unit.is_unsafe,
+                count: 0,
+            };
+            counter.visit_block(block);
+            counts.expressions += counter.count;
+        }
+        Syntax::Init { expr, .. } => {
+            let mut counter = ExprCounter {
+                in_unsafe: false,
+                count: 0,
+            };
+            counter.visit_expr(expr);
+            counts.expressions += counter.count;
+        }
+        Syntax::Impl(_) => counts.impls += usize::from(unit.is_unsafe),
+        Syntax::Trait(_) => counts.traits += usize::from(unit.is_unsafe),
+        Syntax::ExternBlock(_) => {}
+    });
+    counts
+}
+
+/// Counts expressions in unsafe context.
+struct ExprCounter {
+    in_unsafe: bool,
+    count: usize,
+}
+
+impl<'ast> Visit<'ast> for ExprCounter {
+    fn visit_expr(&mut self, node: &'ast syn::Expr) {
+        if self.in_unsafe {
+            self.count += 1;
+        }
+        visit::visit_expr(self, node);
+    }
+
+    fn visit_expr_unsafe(&mut self, node: &'ast syn::ExprUnsafe) {
+        let outer = self.in_unsafe;
+        self.in_unsafe = true;
+        visit::visit_expr_unsafe(self, node);
+        self.in_unsafe = outer;
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        // A function nested in a body starts its own context
+        let outer = self.in_unsafe;
+        self.in_unsafe = node.sig.unsafety.is_some();
+        visit::visit_item_fn(self, node);
+        self.in_unsafe = outer;
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        for_each_macro_expr(node, |expr| self.visit_expr(expr));
+    }
+}
+
+/// Every `.rs` file below `dir`, skipping build output, hidden dirs and
+/// nested packages such as test fixtures.
+fn rs_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if path.is_dir() {
+            let nested_package = path.join("Cargo.toml").exists();
+            if name != "target" && !name.starts_with('.') && !nested_package {
+                rs_files(&path, out)?;
+            }
+        } else if name.ends_with(".rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl GeigerReport {
+    /// The report as geiger prints it: a dependency tree with `x/y`
+    /// columns, one per workspace member. Packages already shown in a tree are
+    /// marked `(*)` and not expanded again.
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        out.push_str("Metric output format: x/y\n");
+        out.push_str("    x = unsafe code used by the build\n");
+        out.push_str("    y = total unsafe code found in the crate\n\n");
+        out.push_str("Symbols:\n");
+        out.push_str("    :) = No `unsafe` usage found, declares #![forbid(unsafe_code)]\n");
+        out.push_str("    ?  = No `unsafe` usage found, missing #![forbid(unsafe_code)]\n");
+        out.push_str("    !  = `unsafe` usage found\n\n");
+        let _ = writeln!(
+            out,
+            "{:<11}{:<13}{:<7}{:<8}{:<9}   Dependency",
+            "Functions", "Expressions", "Impls", "Traits", "Methods"
+        );
+        out.push('\n');
+
+        let by_key: BTreeMap<String, &PackageReport> =
+            self.packages.iter().map(|p| (p.key(), p)).collect();
+        for root in &self.roots {
+            let mut shown = BTreeSet::new();
+            self.row(&by_key, root, "", "", &mut shown, &mut out);
+            out.push('\n');
+        }
+
+        let sum = |f: fn(&PackageReport) -> GeigerCounts| {
+            self.packages
+                .iter()
+                .fold(GeigerCounts::default(), |acc, p| acc + f(p))
+        };
+        let (used, total) = (sum(|p| p.used), sum(|p| p.total));
+        let _ = writeln!(out, "{}   TOTAL", columns(&used, &total));
+        out
+    }
+
+    fn row(
+        &self,
+        by_key: &BTreeMap<String, &PackageReport>,
+        key: &str,
+        branch: &str,
+        indent: &str,
+        shown: &mut BTreeSet<String>,
+        out: &mut String,
+    ) {
+        let Some(package) = by_key.get(key) else {
+            return;
+        };
+        let symbol = if !package.used.is_zero() {
+            "! "
+        } else if package.forbids_unsafe {
+            ":)"
+        } else {
+            "? "
+        };
+        let again = !shown.insert(key.to_string());
+        let _ = writeln!(
+            out,
+            "{} {} {}{}{}{}",
+            columns(&package.used, &package.total),
+            symbol,
+            branch,
+            key,
+            if again { " (*)" } else { "" },
+            if package.errors.is_empty() {
+                ""
+            } else {
+                " [unreadable files]"
+            }
+        );
+        if again {
+            return;
+        }
+        let count = package.dependencies.len();
+        for (i, dependency) in package.dependencies.iter().enumerate() {
+            let last = i + 1 == count;
+            let branch = format!("{}{}", indent, if last { "└── " } else { "├── " });
+            let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            self.row(by_key, dependency, &branch, &indent, shown, out);
+        }
+    }
+}
+
+fn columns(used: &GeigerCounts, total: &GeigerCounts) -> String {
+    let pair = |x: usize, y: usize| format!("{}/{}", x, y);
+    format!(
+        "{:<11}{:<13}{:<7}{:<8}{:<9}",
+        pair(used.functions, total.functions),
+        pair(used.expressions, total.expressions),
+        pair(used.impls, total.impls),
+        pair(used.traits, total.traits),
+        pair(used.methods, total.methods),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cfg() {
+        let cfg =
+            Cfg::parse("unix\ntarget_os=\"linux\"\ndebug_assertions\n").with_features(["std"]);
+        let enabled = |source: &str| {
+            let item: syn::ItemFn = syn::parse_str(&format!("{} fn f() {{}}", source)).unwrap();
+            cfg.enabled(&item.attrs)
+        };
+        assert!(enabled("#[cfg(unix)]"));
+        assert!(enabled(
+            "#[cfg(all(target_os = \"linux\", feature = \"std\"))]"
+        ));
+        assert!(enabled("#[cfg(not(test))]"));
+        assert!(!enabled("#[cfg(windows)]"));
+        assert!(!enabled(
+            "#[cfg(any(feature = \"stats\", target_os = \"macos\"))]"
+        ));
+        assert!(!enabled("#[cfg(unix)] #[cfg(test)]"));
+    }
+
+    #[test]
+    fn test_cfg_from_cargo_env() {
+        let vars = [
+            ("CARGO_CFG_UNIX", ""),
+            ("CARGO_CFG_TARGET_OS", "linux"),
+            ("CARGO_CFG_TARGET_FEATURE", "fxsr,sse2"),
+            ("CARGO_PKG_NAME", "krate"),
+        ];
+        let cfg = Cfg::from_cargo_env(vars.map(|(k, v)| (k.to_string(), v.to_string())));
+        let host = Cfg::parse(
+            "unix\ntarget_os=\"linux\"\ntarget_feature=\"fxsr\"\ntarget_feature=\"sse2\"",
+        );
+        assert_eq!(cfg.set, host.set);
+    }
+
+    #[test]
+    fn test_used_and_total() {
+        let syntax = syn::parse_file(
+            r#"
+            pub unsafe fn raw(p: *const u8) -> u8 { *p }
+            pub fn read(p: &u8) -> u8 { unsafe { raw(p) } }
+            #[cfg(test)]
+            mod tests {
+                fn t() { unsafe { super::raw(&1) }; }
+            }
+            unsafe impl Send for Buf {}
+            impl Buf { unsafe fn get(&self) {} }
+            unsafe trait Zeroable {}
+            "#,
+        )
+        .unwrap();
+        let total = counts(&syntax);
+        assert_eq!(
+            total,
+            GeigerCounts {
+                functions: 1,
+                // `*p`, `p`; `raw(p)`, `raw`, `p`; `super::raw(&1)`,
+                // `super::raw`, `&1`, `1`
+                expressions: 9,
+                impls: 1,
+                traits: 1,
+                methods: 1,
+            }
+        );
+
+        let mut used = syntax.clone();
+        strip(&mut used.items, &Cfg::parse("unix"));
+        assert_eq!(counts(&used).expressions, 5);
+    }
+}
@@ -0,0 +1,128 @@
+//! Reading a workspace as it was at a git revision.
+//!
+//! Only the files the audit reads (`Cargo.toml` and `.rs` files) are
+//! exported, from the object database, so neither the working tree nor the
+//! index is touched.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Runs git in `dir` and returns its stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// The full commit id `rev` names in the repository containing `dir`.
+pub fn resolve(dir: &Path, rev: &str) -> Result<String, String> {
+    let commit = format!("{}^{{commit}}", rev);
+    let out = git(dir, &["rev-parse", "--verify", "--quiet", &commit])
+        .map_err(|_| format!("{}: not a commit", rev))?;
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+/// Writes the workspace at `root` as of `commit` into `dest`, keeping its
+/// layout: `root/a/b.rs` becomes `dest/a/b.rs`.
+pub fn export(root: &Path, commit: &str, dest: &Path) -> Result<(), String> {
+    // Where `root` sits inside the repository, e.g. `rust/`
+    let prefix = String::from_utf8_lossy(&git(root, &["rev-parse", "--show-prefix"])?)
+        .trim()
+        .to_string();
+    let toplevel = String::from_utf8_lossy(&git(root, &["rev-parse", "--show-toplevel"])?)
+        .trim()
+        .to_string();
+    let listing = git(
+        Path::new(&toplevel),
+        &["ls-tree", "-r", "-z", "--full-tree", commit],
+    )?;
+
+    // (object id, path below root)
+    let mut wanted = Vec::new();
+    for entry in listing.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+        let entry = String::from_utf8_lossy(entry);
+        let Some((meta, path)) = entry.split_once('\t') else {
+            continue;
+        };
+        let fields: Vec<&str> = meta.split(' ').collect();
+        let [mode, "blob", id] = fields[..] else {
+            continue;
+        };
+        let Some(path) = path.strip_prefix(prefix.as_str()) else {
+            continue;
+        };
+        // Symlinks are stored as blobs holding the target path
+        let audited = path.ends_with(".rs") || path.rsplit('/').next() == Some("Cargo.toml");
+        if mode != "120000" && audited {
+            wanted.push((id.to_string(), path.to_string()));
+        }
+    }
+
+    let contents = cat_blobs(Path::new(&toplevel), &wanted)?;
+    for ((_, path), content) in wanted.iter().zip(contents) {
+        let target = dest.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::write(&target, content).map_err(|e| format!("{}: {}", target.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Reads blobs in one `git cat-file --batch` run.
+fn cat_blobs(toplevel: &Path, blobs: &[(String, String)]) -> Result<Vec<Vec<u8>>, String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(toplevel)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    let request: String = blobs.iter().map(|(id, _)| format!("{}\n", id)).collect();
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Write from another thread: git's output fills the pipe long before
+    // it has read every request
+    let writer = std::thread::spawn(move || stdin.write_all(request.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("git cat-file: {}", e))?;
+    writer
+        .join()
+        .expect("writer thread panicked")
+        .map_err(|e| format!("git cat-file: {}", e))?;
+
+    // Each blob is `<id> blob <size>\n<content>\n`
+    let mut out = output.stdout.as_slice();
+    let mut contents = Vec::new();
+    for (id, path) in blobs {
+        let header_end = out
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| format!("git cat-file: truncated output at {}", path))?;
+        let header = String::from_utf8_lossy(&out[..header_end]).to_string();
+        let size: usize = match header.split(' ').collect::<Vec<_>>()[..] {
+            [got, "blob", size] if got == id => size.parse().map_err(|_| header.clone())?,
+            _ => return Err(format!("git cat-file: unexpected header {:?}", header)),
+        };
+        let body = out
+            .get(header_end + 1..header_end + 1 + size)
+            .ok_or_else(|| format!("git cat-file: truncated output at {}", path))?;
+        contents.push(body.to_vec());
+        out = out.get(header_end + size + 2..).unwrap_or_default();
+    }
+    Ok(contents)
+}
@@ -0,0 +1,405 @@
+//! The unsafe propagation graph.
+//!
+//! Every function is classified by how it handles unsafety:
+//!
+//! | Class        | Meaning                                                    |
+//! |--------------|------------------------------------------------------------|
+//! | `source`     | `unsafe fn` that calls no other unsafe workspace function  |
+//! | `propagator` | `unsafe fn` that passes on obligations of unsafe callees   |
+//! | `suppressor` | safe fn containing `unsafe { }`: obligations end here      |
+//! | `safe`       | neither                                                    |
+//!
+//! Edges point from callee to caller, the direction obligations travel:
+//! `raw_alloc -> mid_level_alloc_zeroed -> SafeBuffer::new`. Only calls out
+//! of non-safe functions are edges, so the graph shows where each obligation
+//! starts, how far it propagates, and where it is discharged.
+//!
+//! Calls are resolved by name, without type information: paths such as
+//! `raw_alloc`, `SafeBuffer::new` or `propagation_chain::cleanup` resolve to
+//! the matching workspace function, preferring the caller's own module and
+//! crate. Method calls resolve only on `self`, to methods of the caller's
+//! own type. Calls into `std` and other dependencies are not shown.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use serde::Serialize;
+use syn::visit::{self, Visit};
+
+use crate::blocks;
+use crate::count::for_each_macro_expr;
+use crate::items::{self, ItemKind, Syntax};
+use crate::workspace::SourceFile;
+
+/// How a function handles unsafety.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Class {
+    Source,
+    Propagator,
+    Suppressor,
+    Safe,
+}
+
+impl Class {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Class::Source => "source",
+            Class::Propagator => "propagator",
+            Class::Suppressor => "suppressor",
+            Class::Safe => "safe",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Node {
+    /// Full path, also the node's id
+    pub path: String,
+    pub kind: ItemKind,
+    pub class: Class,
+    pub file: String,
+    pub line: usize,
+}
+
+/// What happens to an obligation along an edge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// An `unsafe fn` calls an unsafe fn: the obligation moves on
+    Propagates,
+    /// A safe fn calls an unsafe fn: the obligation is discharged
+    Discharges,
+    /// A caller uses a suppressor, which already discharged it
+    Calls,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Edge {
+    /// The callee
+    pub from: String,
+    /// The caller
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A call as written, before resolution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Call {
+    /// `a::b::f(..)`: the path segments
+    Path(Vec<String>),
+    /// `receiver.f(..)`, and whether the receiver is `self`
+    Method { name: String, on_self: bool },
+}
+
+/// A function and what it calls, owned so files can be dropped.
+struct Function {
+    node: Node,
+    name: String,
+    module: String,
+    self_ty: Option<String>,
+    is_unsafe: bool,
+    has_unsafe_blocks: bool,
+    calls: Vec<Call>,
+}
+
+/// Builds the graph over every source file.
+pub fn build(files: &[SourceFile]) -> Result<Graph, String> {
+    let mut functions = Vec::new();
+    for file in files {
+        let syntax = file.parse()?;
+        collect(&syntax, file, &mut functions);
+    }
+    Ok(link(functions))
+}
+
+fn collect(syntax: &syn::File, file: &SourceFile, out: &mut Vec<Function>) {
+    items::walk(syntax, &file.module_path, &mut |unit| {
+        let Syntax::Fn { block, .. } = unit.syntax else {
+            return;
+        };
+        let mut collector = CallCollector(Vec::new());
+        collector.visit_block(block);
+        out.push(Function {
+            node: Node {
+                path: unit.path.clone(),
+                kind: unit.kind,
+                class: Class::Safe,
+                file: file.rel_path.clone(),
+                line: unit.line,
+            },
+            name: unit.name.clone(),
+            module: unit.module.clone(),
+            self_ty: unit.self_ty.clone(),
+            is_unsafe: unit.is_unsafe,
+            has_unsafe_blocks: !blocks::unsafe_blocks(&unit).is_empty(),
+            calls: collector.0,
+        });
+    });
+}
+
+fn link(mut functions: Vec<Function>) -> Graph {
+    let callees: Vec<BTreeSet<usize>> = functions
+        .iter()
+        .map(|caller| {
+            caller
+                .calls
+                .iter()
+                .flat_map(|call| resolve(&functions, caller, call))
+                .filter(|&callee| !std::ptr::eq(&functions[callee], caller))
+                .collect()
+        })
+        .collect();
+
+    for i in 0..functions.len() {
+        let calls_unsafe = callees[i].iter().any(|&c| functions[c].is_unsafe);
+        let function = &mut functions[i];
+        function.node.class = match (function.is_unsafe, function.has_unsafe_blocks) {
+            (true, _) if calls_unsafe => Class::Propagator,
+            (true, _) => Class::Source,
+            (false, true) => Class::Suppressor,
+            (false, false) => Class::Safe,
+        };
+    }
+
+    let mut edges = BTreeSet::new();
+    let mut connected = BTreeSet::new();
+    for (caller, callees) in callees.iter().enumerate() {
+        for &callee in callees {
+            let kind = match (functions[callee].is_unsafe, functions[caller].is_unsafe) {
+                (true, true) => EdgeKind::Propagates,
+                (true, false) => EdgeKind::Discharges,
+                (false, _) if functions[callee].node.class == Class::Suppressor => EdgeKind::Calls,
+                _ => continue,
+            };
+            edges.insert(Edge {
+                from: functions[callee].node.path.clone(),
+                to: functions[caller].node.path.clone(),
+                kind,
+            });
+            connected.insert(callee);
+            connected.insert(caller);
+        }
+    }
+
+    let nodes = functions
+        .into_iter()
+        .enumerate()
+        .filter(|(i, f)| f.node.class != Class::Safe || connected.contains(i))
+        .map(|(_, f)| f.node)
+        .collect();
+    Graph {
+        nodes,
+        edges: edges.into_iter().collect(),
+    }
+}
+
+/// Workspace functions a call may refer to.
+fn resolve(functions: &[Function], caller: &Function, call: &Call) -> Vec<usize> {
+    match call {
+        // Without types, only calls on `self` can be told apart from the
+        // many std methods with the same names (`read`, `write`, `get`)
+        Call::Method { name, on_self } if *on_self && caller.self_ty.is_some() => {
+            named(functions, name)
+                .filter(|(_, f)| f.self_ty == caller.self_ty)
+                .map(|(i, _)| i)
+                .collect()
+        }
+        Call::Method { .. } => Vec::new(),
+        Call::Path(segments) => {
+            let name = segments.last().expect("paths are never empty");
+            let qualifier = segments.len().checked_sub(2).map(|i| segments[i].as_str());
+            match qualifier {
+                Some("Self") => named(functions, name)
+                    .filter(|(_, f)| f.self_ty.is_some() && f.self_ty == caller.self_ty)
+                    .map(|(i, _)| i)
+                    .collect(),
+                Some("crate" | "self" | "super") | None => best_scope(
+                    caller,
+                    named(functions, name).filter(|(_, f)| f.self_ty.is_none()),
+                ),
+                Some(qualifier) => named(functions, name)
+                    .filter(|(_, f)| match &f.self_ty {
+                        Some(ty) => ty == qualifier,
+                        None => f.module.rsplit("::").next() == Some(qualifier),
+                    })
+                    .map(|(i, _)| i)
+                    .collect(),
+            }
+        }
+    }
+}
+
+fn named<'a>(
+    functions: &'a [Function],
+    name: &'a str,
+) -> impl Iterator<Item = (usize, &'a Function)> + 'a {
+    functions
+        .iter()
+        .enumerate()
+        .filter(move |(_, f)| f.name == name)
+}
+
+/// Keeps the candidates closest to `caller`: its own module, then each
+/// enclosing module in turn (glob imports such as `use super::*`), then
+/// anywhere in the workspace.
+fn best_scope<'a>(
+    caller: &Function,
+    candidates: impl Iterator<Item = (usize, &'a Function)>,
+) -> Vec<usize> {
+    let candidates: Vec<(usize, &Function)> = candidates.collect();
+    let mut scope = caller.module.as_str();
+    loop {
+        let here: Vec<usize> = candidates
+            .iter()
+            .filter(|(_, f)| f.module == scope)
+            .map(|(i, _)| *i)
+            .collect();
+        if !here.is_empty() {
+            return here;
+        }
+        match scope.rfind("::") {
+            Some(at) => scope = &scope[..at],
+            None => return candidates.iter().map(|(i, _)| *i).collect(),
+        }
+    }
+}
+
+struct CallCollector(Vec<Call>);
+
+impl<'ast> Visit<'ast> for CallCollector {
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(path) = &*node.func {
+            let segments = path
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            self.0.push(Call::Path(segments));
+        }
+        visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        let on_self = matches!(&*node.receiver, syn::Expr::Path(p) if p.path.is_ident("self"));
+        self.0.push(Call::Method {
+            name: node.method.to_string(),
+            on_self,
+        });
+        visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        for_each_macro_expr(node, |expr| self.visit_expr(expr));
+    }
+}
+
+impl Graph {
+    /// Renders the graph in Graphviz DOT.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph unsafe_propagation {\n");
+        dot.push_str("    rankdir=LR;\n");
+        dot.push_str("    node [shape=box, style=filled, fontname=\"monospace\"];\n");
+        for node in &self.nodes {
+            let color = match node.class {
+                Class::Source => "#f4cccc",
+                Class::Propagator => "#fce5cd",
+                Class::Suppressor => "#d9ead3",
+                Class::Safe => "#ffffff",
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\\n{}\", fillcolor=\"{}\"];",
+                node.path,
+                node.path,
+                node.class.as_str(),
+                color
+            );
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Propagates => "color=\"#cc0000\"",
+                EdgeKind::Discharges => "color=\"#38761d\", penwidth=2, label=\"discharged\"",
+                EdgeKind::Calls => "color=\"#999999\"",
+            };
+            let _ = writeln!(dot, "    \"{}\" -> \"{}\" [{}];", edge.from, edge.to, style);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(source: &str) -> Graph {
+        let syntax = syn::parse_file(source).unwrap();
+        let file = SourceFile {
+            package: "krate".to_string(),
+            rel_path: "src/lib.rs".to_string(),
+            path: "src/lib.rs".into(),
+            module_path: vec!["krate".to_string()],
+        };
+        let mut functions = Vec::new();
+        collect(&syntax, &file, &mut functions);
+        link(functions)
+    }
+
+    #[test]
+    fn test_chain() {
+        let graph = graph(
+            r#"
+            unsafe fn raw_alloc() -> *mut i32 { alloc() }
+            fn zeroed() -> *mut i32 { unsafe { raw_alloc() } }
+            pub struct Buf;
+            impl Buf {
+                pub fn new() -> Buf { zeroed(); Buf }
+                pub fn again() -> Buf { Self::new() }
+            }
+            mod chain {
+                use super::*;
+                pub unsafe fn level1() -> *mut i32 { raw_alloc() }
+                pub fn unrelated() {}
+            }
+            "#,
+        );
+
+        let class = |path: &str| graph.nodes.iter().find(|n| n.path == path).unwrap().class;
+        assert_eq!(class("krate::raw_alloc"), Class::Source);
+        assert_eq!(class("krate::zeroed"), Class::Suppressor);
+        assert_eq!(class("krate::Buf::new"), Class::Safe);
+        assert_eq!(class("krate::chain::level1"), Class::Propagator);
+        assert!(graph
+            .nodes
+            .iter()
+            .all(|n| n.path != "krate::chain::unrelated"));
+        assert!(graph.nodes.iter().all(|n| n.path != "krate::Buf::again"));
+
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.kind))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                (
+                    "krate::raw_alloc",
+                    "krate::chain::level1",
+                    EdgeKind::Propagates
+                ),
+                ("krate::raw_alloc", "krate::zeroed", EdgeKind::Discharges),
+                ("krate::zeroed", "krate::Buf::new", EdgeKind::Calls),
+            ]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! Stable content hashes.
+//!
+//! Hashes are compared across revisions and stored in files, so they must
+//! not depend on the platform or toolchain the way `DefaultHasher` may.
+//! 64-bit FNV-1a is enough to notice that text changed; nothing here
+//! defends against someone crafting collisions.
+
+const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hex FNV-1a hash of `text`.
+pub fn hash(text: &str) -> String {
+    let mut hash = OFFSET;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(PRIME);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Hash of a comment, ignoring how it is wrapped and indented.
+pub fn comment_hash(comment: &str) -> String {
+    hash(&comment.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(hash(""), "cbf29ce484222325");
+        assert_eq!(hash("a"), "af63dc4c8601ec8c");
+        assert_eq!(comment_hash("SAFETY: a\n  b"), comment_hash("SAFETY: a b"));
+    }
+}
@@ -0,0 +1,389 @@
+//! Walking a parsed file item by item.
+//!
+//! Every analysis in this crate works on the same units: functions, methods,
+//! trait methods with a default body, impl blocks, traits, `extern` blocks
+//! and static/const initializers, each named by its full path
+//! (`memory_lib::SafeBuffer::get`, `memory_lib::<SafeBuffer as Send>`).
+
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Block, Expr, ImplItem, Item, ItemForeignMod, ItemImpl, ItemTrait, Signature,
+    TraitItem, Type, Visibility,
+};
+
+/// What kind of item a unit is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Function,
+    Method,
+    TraitMethod,
+    Impl,
+    Trait,
+    ExternBlock,
+    Static,
+    Const,
+}
+
+impl ItemKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ItemKind::Function => "fn",
+            ItemKind::Method => "method",
+            ItemKind::TraitMethod => "trait method",
+            ItemKind::Impl => "impl",
+            ItemKind::Trait => "trait",
+            ItemKind::ExternBlock => "extern block",
+            ItemKind::Static => "static",
+            ItemKind::Const => "const",
+        }
+    }
+
+    /// True for the kinds that have a signature and a body.
+    pub fn is_fn(self) -> bool {
+        matches!(
+            self,
+            ItemKind::Function | ItemKind::Method | ItemKind::TraitMethod
+        )
+    }
+}
+
+/// The syntax behind a unit.
+#[derive(Clone, Copy)]
+pub enum Syntax<'a> {
+    Fn {
+        attrs: &'a [Attribute],
+        vis: Option<&'a Visibility>,
+        sig: &'a Signature,
+        block: &'a Block,
+    },
+    Impl(&'a ItemImpl),
+    Trait(&'a ItemTrait),
+    ExternBlock(&'a ItemForeignMod),
+    Init {
+        attrs: &'a [Attribute],
+        expr: &'a Expr,
+    },
+}
+
+/// One auditable item.
+#[derive(Clone)]
+pub struct Unit<'a> {
+    /// Full path, starting with the module path
+    pub path: String,
+    /// Path of the innermost module containing the item
+    pub module: String,
+    /// The item's own name, without module or type prefixes
+    pub name: String,
+    pub kind: ItemKind,
+    /// Line of the item's keyword (`fn`, `impl`, `trait`, `extern`, ...)
+    pub line: usize,
+    /// First line of the item including its attributes and doc comments
+    pub start_line: usize,
+    /// Last line of the item
+    pub end_line: usize,
+    /// Whether the item itself is declared `unsafe`
+    pub is_unsafe: bool,
+    /// For impls and their methods: the implementing type
+    pub self_ty: Option<String>,
+    /// For trait impls and their methods: the trait implemented
+    pub impl_trait: Option<String>,
+    pub syntax: Syntax<'a>,
+}
+
+impl Unit<'_> {
+    pub fn attrs(&self) -> &[Attribute] {
+        match self.syntax {
+            Syntax::Fn { attrs, .. } | Syntax::Init { attrs, .. } => attrs,
+            Syntax::Impl(item) => &item.attrs,
+            Syntax::Trait(item) => &item.attrs,
+            Syntax::ExternBlock(item) => &item.attrs,
+        }
+    }
+
+    /// True if the item is visible outside its crate (plain `pub`).
+    pub fn is_public(&self) -> bool {
+        match self.syntax {
+            Syntax::Fn { vis, .. } => matches!(vis, Some(Visibility::Public(_))),
+            Syntax::Trait(item) => matches!(item.vis, Visibility::Public(_)),
+            _ => false,
+        }
+    }
+}
+
+/// Calls `f` for every unit in `file`, whose module path is `module`.
+pub fn walk<'a>(file: &'a syn::File, module: &[String], f: &mut impl FnMut(Unit<'a>)) {
+    walk_items(&file.items, &mut module.to_vec(), f);
+}
+
+fn walk_items<'a>(items: &'a [Item], module: &mut Vec<String>, f: &mut impl FnMut(Unit<'a>)) {
+    for item in items {
+        let prefix = module.join("::");
+        match item {
+            Item::Fn(func) => f(fn_unit(
+                &prefix,
+                &prefix,
+                ItemKind::Function,
+                &func.attrs,
+                Some(&func.vis),
+                &func.sig,
+                &func.block,
+                item.span(),
+            )),
+            Item::Impl(imp) => {
+                let self_ty = type_name(&imp.self_ty);
+                let impl_trait = imp.trait_.as_ref().map(|(_, path, _)| path_name(path));
+                let (impl_name, method_prefix) = match &impl_trait {
+                    Some(tr) => {
+                        let name = format!("<{} as {}>", self_ty, tr);
+                        (name.clone(), name)
+                    }
+                    None => (format!("impl {}", self_ty), self_ty.clone()),
+                };
+                f(Unit {
+                    path: format!("{}::{}", prefix, impl_name),
+                    module: prefix.clone(),
+                    name: impl_name,
+                    kind: ItemKind::Impl,
+                    line: line(imp.impl_token.span),
+                    start_line: line(item.span()),
+                    end_line: end_line(item.span()),
+                    is_unsafe: imp.unsafety.is_some(),
+                    self_ty: Some(self_ty.clone()),
+                    impl_trait: impl_trait.clone(),
+                    syntax: Syntax::Impl(imp),
+                });
+                let owner = format!("{}::{}", prefix, method_prefix);
+                for impl_item in &imp.items {
+                    if let ImplItem::Fn(method) = impl_item {
+                        f(Unit {
+                            self_ty: Some(self_ty.clone()),
+                            impl_trait: impl_trait.clone(),
+                            ..fn_unit(
+                                &prefix,
+                                &owner,
+                                ItemKind::Method,
+                                &method.attrs,
+                                Some(&method.vis),
+                                &method.sig,
+                                &method.block,
+                                impl_item.span(),
+                            )
+                        });
+                    }
+                }
+            }
+            Item::Trait(tr) => {
+                f(Unit {
+                    path: format!("{}::{}", prefix, tr.ident),
+                    module: prefix.clone(),
+                    name: tr.ident.to_string(),
+                    kind: ItemKind::Trait,
+                    line: line(tr.trait_token.span),
+                    start_line: line(item.span()),
+                    end_line: end_line(item.span()),
+                    is_unsafe: tr.unsafety.is_some(),
+                    self_ty: None,
+                    impl_trait: None,
+                    syntax: Syntax::Trait(tr),
+                });
+                let owner = format!("{}::{}", prefix, tr.ident);
+                for trait_item in &tr.items {
+                    if let TraitItem::Fn(method) = trait_item {
+                        if let Some(block) = &method.default {
+                            f(fn_unit(
+                                &prefix,
+                                &owner,
+                                ItemKind::TraitMethod,
+                                &method.attrs,
+                                None,
+                                &method.sig,
+                                block,
+                                trait_item.span(),
+                            ));
+                        }
+                    }
+                }
+            }
+            Item::ForeignMod(foreign) => {
+                let abi = foreign
+                    .abi
+                    .name
+                    .as_ref()
+                    .map_or("C".to_string(), |name| name.value());
+                let name = format!("extern \"{}\"", abi);
+                f(Unit {
+                    path: format!("{}::{}", prefix, name),
+                    module: prefix.clone(),
+                    name,
+                    kind: ItemKind::ExternBlock,
+                    line: line(foreign.abi.extern_token.span),
+                    start_line: line(item.span()),
+                    end_line: end_line(item.span()),
+                    is_unsafe: true,
+                    self_ty: None,
+                    impl_trait: None,
+                    syntax: Syntax::ExternBlock(foreign),
+                });
+            }
+            Item::Static(stat) => f(init_unit(
+                &prefix,
+                ItemKind::Static,
+                &stat.ident,
+                &stat.attrs,
+                &stat.expr,
+                item.span(),
+            )),
+            Item::Const(cons) => f(init_unit(
+                &prefix,
+                ItemKind::Const,
+                &cons.ident,
+                &cons.attrs,
+                &cons.expr,
+                item.span(),
+            )),
+            Item::Mod(module_item) => {
+                if let Some((_, items)) = &module_item.content {
+                    module.push(module_item.ident.to_string());
+                    walk_items(items, module, f);
+                    module.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fn_unit<'a>(
+    module: &str,
+    owner: &str,
+    kind: ItemKind,
+    attrs: &'a [Attribute],
+    vis: Option<&'a Visibility>,
+    sig: &'a Signature,
+    block: &'a Block,
+    span: Span,
+) -> Unit<'a> {
+    Unit {
+        path: format!("{}::{}", owner, sig.ident),
+        module: module.to_string(),
+        name: sig.ident.to_string(),
+        kind,
+        line: line(sig.fn_token.span),
+        start_line: line(span),
+        end_line: end_line(span),
+        is_unsafe: sig.unsafety.is_some(),
+        self_ty: None,
+        impl_trait: None,
+        syntax: Syntax::Fn {
+            attrs,
+            vis,
+            sig,
+            block,
+        },
+    }
+}
+
+fn init_unit<'a>(
+    module: &str,
+    kind: ItemKind,
+    ident: &syn::Ident,
+    attrs: &'a [Attribute],
+    expr: &'a Expr,
+    span: Span,
+) -> Unit<'a> {
+    Unit {
+        path: format!("{}::{}", module, ident),
+        module: module.to_string(),
+        name: ident.to_string(),
+        kind,
+        line: line(ident.span()),
+        start_line: line(span),
+        end_line: end_line(span),
+        is_unsafe: false,
+        self_ty: None,
+        impl_trait: None,
+        syntax: Syntax::Init { attrs, expr },
+    }
+}
+
+pub fn line(span: Span) -> usize {
+    span.start().line
+}
+
+pub fn end_line(span: Span) -> usize {
+    span.end().line
+}
+
+/// Short, readable name for a type: `SafeBuffer`, `FixedBuffer`, `[T]`.
+pub fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path_name(&path.path),
+        Type::Reference(reference) => type_name(&reference.elem),
+        Type::Slice(slice) => format!("[{}]", type_name(&slice.elem)),
+        Type::Array(array) => format!("[{}; {}]", type_name(&array.elem), tokens(&array.len)),
+        other => tokens(other),
+    }
+}
+
+/// Last segment of a path, without generic arguments.
+pub fn path_name(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map_or_else(|| tokens(path), |segment| segment.ident.to_string())
+}
+
+/// Token text with the spacing `to_string` inserts removed.
+pub fn tokens(node: &impl ToTokens) -> String {
+    node.to_token_stream()
+        .to_string()
+        .replace(" :: ", "::")
+        .replace(" ;", ";")
+        .replace("< ", "<")
+        .replace(" >", ">")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_paths() {
+        let file = syn::parse_file(
+            r#"
+            pub fn free() {}
+            impl Buf { fn get(&self) {} }
+            unsafe impl Send for Buf {}
+            impl Drop for Buf<T> { fn drop(&mut self) {} }
+            pub mod inner {
+                unsafe trait Marker { fn provided() {} fn required(); }
+                extern "C" { fn abs(x: i32) -> i32; }
+                static X: i32 = 0;
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut seen = Vec::new();
+        walk(&file, &["lib".to_string()], &mut |unit| {
+            seen.push((unit.path, unit.kind, unit.is_unsafe));
+        });
+        let expected = [
+            ("lib::free", ItemKind::Function, false),
+            ("lib::impl Buf", ItemKind::Impl, false),
+            ("lib::Buf::get", ItemKind::Method, false),
+            ("lib::<Buf as Send>", ItemKind::Impl, true),
+            ("lib::<Buf as Drop>", ItemKind::Impl, false),
+            ("lib::<Buf as Drop>::drop", ItemKind::Method, false),
+            ("lib::inner::Marker", ItemKind::Trait, true),
+            ("lib::inner::Marker::provided", ItemKind::TraitMethod, false),
+            ("lib::inner::extern \"C\"", ItemKind::ExternBlock, true),
+            ("lib::inner::X", ItemKind::Static, false),
+        ];
+        let seen: Vec<_> = seen.iter().map(|(p, k, u)| (p.as_str(), *k, *u)).collect();
+        assert_eq!(seen, expected);
+    }
+}
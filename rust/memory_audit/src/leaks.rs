@@ -0,0 +1,276 @@
+//! Finding leaky suppressions.
+//!
+//! A safe function suppresses unsafety: its callers need no `unsafe`. The
+//! claim is hollow when a raw pointer crosses the safe signature:
+//!
+//! - A safe public fn that returns a `*const T`/`*mut T`/`NonNull<T>` hands
+//!   out memory that, unless some safe function takes it back, can only be
+//!   released through an unsafe one. `propagation_chain::level3_suppress` is
+//!   the example: safe to call, but freeing its result needs `unsafe`
+//!   `cleanup`, so the obligation escapes the suppression boundary.
+//! - A safe public fn that takes a raw pointer trusts a pointer that safe
+//!   code can forge. Whether it dereferences the pointer itself, hands it to
+//!   an unsafe fn behind a safe wrapper or to another crate's unsafe code,
+//!   the obligation to pass a valid pointer now sits with callers who were
+//!   told there is none, so every such fn is reported.
+//!
+//! Each returned pointer is linked to the function that releases it: a fn
+//! named like a release (`free`, `dealloc`, `cleanup`, `from_raw_parts`, ...)
+//! taking the same pointee type, nearest to the returning function.
+
+use syn::{FnArg, GenericArgument, PathArguments, ReturnType, Type};
+
+use crate::finding::{Finding, Related, Rule};
+use crate::items::{self, Syntax};
+use crate::workspace::SourceFile;
+
+/// Substrings of function names that release memory.
+const RELEASE_NAMES: &[&str] = &[
+    "free", "dealloc", "release", "cleanup", "destroy", "dispose", "drop", "from_raw", "reclaim",
+];
+
+/// A function's pointer-related facts, owned so files can be dropped.
+#[derive(Clone, Debug)]
+struct Function {
+    path: String,
+    name: String,
+    module: String,
+    file: String,
+    line: usize,
+    is_unsafe: bool,
+    is_public: bool,
+    /// Pointee type of a raw pointer in the return type
+    returns: Option<String>,
+    /// Pointee types of raw pointer parameters
+    takes: Vec<String>,
+}
+
+impl Function {
+    fn is_release(&self) -> bool {
+        let name = self.name.to_lowercase();
+        RELEASE_NAMES.iter().any(|release| name.contains(release))
+    }
+}
+
+/// Finds leaky suppressions in every source file.
+pub fn leaks(files: &[SourceFile]) -> Result<Vec<Finding>, String> {
+    let mut functions = Vec::new();
+    for file in files {
+        let syntax = file.parse()?;
+        collect(&syntax, file, &mut functions);
+    }
+    Ok(analyze(&functions))
+}
+
+fn collect(syntax: &syn::File, file: &SourceFile, out: &mut Vec<Function>) {
+    items::walk(syntax, &file.module_path, &mut |unit| {
+        let Syntax::Fn { sig, .. } = unit.syntax else {
+            return;
+        };
+        // Trait impls implement someone else's signature
+        if unit.impl_trait.is_some() {
+            return;
+        }
+        let returns = match &sig.output {
+            ReturnType::Type(_, ty) => pointee(ty),
+            ReturnType::Default => None,
+        };
+        let takes = sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                FnArg::Typed(arg) => pointee(&arg.ty),
+                FnArg::Receiver(_) => None,
+            })
+            .collect();
+        out.push(Function {
+            path: unit.path.clone(),
+            name: unit.name.clone(),
+            module: unit.module.clone(),
+            file: file.rel_path.clone(),
+            line: unit.line,
+            is_unsafe: unit.is_unsafe,
+            is_public: unit.is_public(),
+            returns,
+            takes,
+        });
+    });
+}
+
+fn analyze(functions: &[Function]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for function in functions.iter().filter(|f| f.is_public && !f.is_unsafe) {
+        if let Some(pointee) = &function.returns {
+            let releases: Vec<&Function> = functions
+                .iter()
+                .filter(|f| f.is_release() && f.takes.contains(pointee))
+                .collect();
+            let safe_release = releases.iter().any(|f| f.is_public && !f.is_unsafe);
+            if !safe_release {
+                let release = nearest(function, &releases);
+                let message = match release {
+                    Some(release) => format!(
+                        "safe fn `{}` returns a raw pointer to {} that only unsafe `{}` can release",
+                        function.name, pointee, release.name
+                    ),
+                    None => format!(
+                        "safe fn `{}` returns a raw pointer to {} and nothing releases it",
+                        function.name, pointee
+                    ),
+                };
+                findings.push(Finding {
+                    rule: Rule::LeakySuppression,
+                    file: function.file.clone(),
+                    line: function.line,
+                    item: function.path.clone(),
+                    message,
+                    related: release.map(|release| Related {
+                        item: release.path.clone(),
+                        file: release.file.clone(),
+                        line: release.line,
+                    }),
+                });
+            }
+        }
+
+        if !function.takes.is_empty() {
+            let mut pointees = function.takes.clone();
+            pointees.sort();
+            pointees.dedup();
+            findings.push(Finding {
+                rule: Rule::LeakySuppression,
+                file: function.file.clone(),
+                line: function.line,
+                item: function.path.clone(),
+                message: format!(
+                    "safe fn `{}` takes a raw pointer to {} that its caller can forge",
+                    function.name,
+                    pointees.join(", ")
+                ),
+                related: None,
+            });
+        }
+    }
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    findings
+}
+
+/// The release function closest to `function` in the module tree.
+fn nearest<'a>(function: &Function, releases: &[&'a Function]) -> Option<&'a Function> {
+    let shared = |release: &Function| {
+        function
+            .module
+            .split("::")
+            .zip(release.module.split("::"))
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    releases
+        .iter()
+        .copied()
+        .max_by_key(|release| (shared(release), !release.is_unsafe))
+}
+
+/// The pointee type of the first raw pointer or `NonNull` in `ty`,
+/// looking inside tuples and generic arguments.
+fn pointee(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Ptr(ptr) => Some(items::type_name(&ptr.elem)),
+        Type::Tuple(tuple) => tuple.elems.iter().find_map(pointee),
+        Type::Paren(paren) => pointee(&paren.elem),
+        Type::Group(group) => pointee(&group.elem),
+        Type::Array(array) => pointee(&array.elem),
+        Type::Slice(slice) => pointee(&slice.elem),
+        Type::Path(path) => {
+            let last = path.path.segments.last()?;
+            let PathArguments::AngleBracketed(args) = &last.arguments else {
+                return None;
+            };
+            let mut types = args.args.iter().filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            });
+            if last.ident == "NonNull" {
+                types.next().map(items::type_name)
+            } else {
+                types.find_map(pointee)
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaks_in(source: &str) -> Vec<(String, Option<String>)> {
+        let syntax = syn::parse_file(source).unwrap();
+        let file = SourceFile {
+            package: "krate".to_string(),
+            rel_path: "src/lib.rs".to_string(),
+            path: "src/lib.rs".into(),
+            module_path: vec!["krate".to_string()],
+        };
+        let mut functions = Vec::new();
+        collect(&syntax, &file, &mut functions);
+        analyze(&functions)
+            .into_iter()
+            .map(|f| (f.item, f.related.map(|r| r.item)))
+            .collect()
+    }
+
+    #[test]
+    fn test_links_to_nearest_release() {
+        let found = leaks_in(
+            r#"
+            unsafe fn raw_dealloc(ptr: *mut i32) {}
+            pub mod chain {
+                pub fn suppress() -> *mut i32 { todo!() }
+                pub unsafe fn cleanup(ptr: *mut i32) {}
+            }
+            pub fn parts() -> (std::ptr::NonNull<u8>, usize) { todo!() }
+            fn private() -> *mut i32 { todo!() }
+            pub unsafe fn propagate() -> *mut i32 { todo!() }
+            "#,
+        );
+        assert_eq!(
+            found,
+            [
+                (
+                    "krate::chain::suppress".to_string(),
+                    Some("krate::chain::cleanup".to_string())
+                ),
+                ("krate::parts".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_safe_release_path_pairs() {
+        let found = leaks_in(
+            r#"
+            pub fn handle() -> *const Node { todo!() }
+            pub fn release_handle(node: *const Node) { registry_remove(node) }
+            "#,
+        );
+        // The handle pairs with its safe release, but the release trusts
+        // whatever pointer it is given
+        assert_eq!(found, [("krate::release_handle".to_string(), None)]);
+    }
+
+    #[test]
+    fn test_pointer_parameters_cross_the_signature() {
+        let found = leaks_in(
+            r#"
+            pub fn peek(node: *const Node) -> i32 { unsafe { (*node).value } }
+            pub fn forward(node: *const Node) -> i32 { read_node(node) }
+            pub fn pass_on(node: std::ptr::NonNull<Node>) { ffi::consume(node.as_ptr()) }
+            pub unsafe fn read_node(node: *const Node) -> i32 { (*node).value }
+            fn private(node: *const Node) -> i32 { unsafe { (*node).value } }
+            "#,
+        );
+        let items: Vec<&str> = found.iter().map(|(item, _)| item.as_str()).collect();
+        assert_eq!(items, ["krate::peek", "krate::forward", "krate::pass_on"]);
+    }
+}
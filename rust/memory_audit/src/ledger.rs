@@ -0,0 +1,536 @@
+//! The reviewed-suppression ledger.
+//!
+//! A `// SAFETY DISCHARGE:` comment says why someone believed a block was
+//! sound, but not whether anyone other than its author checked, nor whether
+//! the code still matches what was checked. A package opts into review
+//! tracking by keeping a `safety-ledger.toml` next to its `Cargo.toml`:
+//!
+//! ```toml
+//! [[entry]]
+//! item = "memory_lib::SafeBuffer::get"
+//! kind = "block"
+//! hash = "5b0c3d6a1f2e4d7c"
+//! reviewer = "jdoe"
+//! date = "2026-10-18"
+//! rationale = "Bounds checked above: index < self.len"
+//! ```
+//!
+//! Every `unsafe { }` block and `unsafe impl` in the package's `src/` needs
+//! an entry whose hash matches its tokens. A block whose code changed has a
+//! stale review; one with no entry was never reviewed. Both fail the check,
+//! so "suppressed after review" and "never audited" can be told apart.
+//!
+//! Hashes cover tokens only: reformatting or rewording comments keeps a
+//! review valid, any change to the code itself does not.
+//!
+//! Entries are only ever written for items a reviewer names: `ledger
+//! --record REVIEWER --item PATH` stamps the new or changed code in `PATH`
+//! and leaves everything else reported, so the ledger never claims a review
+//! that did not happen.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blocks;
+use crate::comments::{self, DISCHARGE, RATIONALE};
+use crate::finding::{Finding, Rule};
+use crate::hash::hash;
+use crate::items::{self, ItemKind};
+use crate::workspace::{self, SourceFile};
+
+pub const FILE_NAME: &str = "safety-ledger.toml";
+
+const HEADER: &str = "\
+# Reviewed unsafe code. Checked by `memory_audit ledger`; after reviewing the
+# new or changed code in an item, record it with
+# `memory_audit ledger --record REVIEWER --item PATH`.
+# See memory_audit/src/ledger.rs.
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewedKind {
+    /// An `unsafe { }` block
+    Block,
+    /// An `unsafe impl`
+    Impl,
+}
+
+impl ReviewedKind {
+    pub fn describe(self) -> &'static str {
+        match self {
+            ReviewedKind::Block => "unsafe block",
+            ReviewedKind::Impl => "unsafe impl",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub item: String,
+    pub kind: ReviewedKind,
+    pub hash: String,
+    pub reviewer: String,
+    pub date: String,
+    pub rationale: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ledger {
+    #[serde(default, rename = "entry")]
+    pub entries: Vec<Entry>,
+}
+
+impl Ledger {
+    pub fn parse(text: &str) -> Result<Ledger, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string(self).expect("ledger entries are plain strings");
+        format!("{}\n{}", HEADER, body)
+    }
+}
+
+/// A piece of unsafe code that needs a review.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reviewable {
+    pub item: String,
+    pub kind: ReviewedKind,
+    pub hash: String,
+    pub file: String,
+    pub line: usize,
+    /// Its safety comment, without the marker
+    pub comment: Option<String>,
+}
+
+/// Lists the unsafe blocks and impls of `files`, in source order.
+pub fn reviewables(files: &[SourceFile]) -> Result<Vec<Reviewable>, String> {
+    let mut out = Vec::new();
+    for file in files {
+        let (text, syntax) = file.parse_with_text()?;
+        let lines: Vec<&str> = text.lines().collect();
+        let comment = |line, marker: &str| {
+            comments::find_marked(&lines, line, marker).map(|c| {
+                c[marker.len()..]
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+        };
+        items::walk(&syntax, &file.module_path, &mut |unit| {
+            if unit.kind == ItemKind::Impl && unit.is_unsafe {
+                if let items::Syntax::Impl(item) = unit.syntax {
+                    out.push(Reviewable {
+                        item: unit.path.clone(),
+                        kind: ReviewedKind::Impl,
+                        hash: hash(&items::tokens(item)),
+                        file: file.rel_path.clone(),
+                        line: unit.line,
+                        comment: comment(unit.start_line, RATIONALE),
+                    });
+                }
+            }
+            for block in blocks::unsafe_blocks(&unit) {
+                out.push(Reviewable {
+                    item: unit.path.clone(),
+                    kind: ReviewedKind::Block,
+                    hash: hash(&block.body),
+                    file: file.rel_path.clone(),
+                    line: block.line,
+                    comment: comment(block.line, DISCHARGE)
+                        .or_else(|| comment(block.stmt_line, DISCHARGE)),
+                });
+            }
+        });
+    }
+    Ok(out)
+}
+
+/// A package's ledger and the code it covers.
+struct Tracked {
+    /// Ledger path relative to the workspace root
+    rel_path: String,
+    path: std::path::PathBuf,
+    text: String,
+    ledger: Ledger,
+    reviewables: Vec<Reviewable>,
+}
+
+/// Finds the packages under `root` that keep a ledger.
+fn tracked(root: &Path) -> Result<Vec<Tracked>, String> {
+    let files = workspace::source_files(root)?;
+    let mut out = Vec::new();
+    for package in workspace::packages(root)? {
+        let path = package.dir.join(FILE_NAME);
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let rel_path = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let ledger = Ledger::parse(&text).map_err(|e| format!("{}: {}", rel_path, e))?;
+        let src = package.dir.join("src");
+        let package_files: Vec<SourceFile> = files
+            .iter()
+            .filter(|f| f.package == package.name && f.path.starts_with(&src))
+            .cloned()
+            .collect();
+        out.push(Tracked {
+            rel_path,
+            path,
+            text,
+            ledger,
+            reviewables: reviewables(&package_files)?,
+        });
+    }
+    Ok(out)
+}
+
+/// How the sources line up with the ledger entries.
+struct Matching<'a> {
+    /// Entry for each reviewable: reviewed if the hashes agree, stale if not
+    entries: Vec<Option<&'a Entry>>,
+    /// Entries whose code is gone
+    orphans: Vec<&'a Entry>,
+}
+
+/// Matches entries to code of the same item and kind: equal hashes first,
+/// then what is left in order, so a changed block finds its old review.
+fn match_entries<'a>(ledger: &'a Ledger, reviewables: &[Reviewable]) -> Matching<'a> {
+    let mut left: BTreeMap<(&str, ReviewedKind), Vec<&Entry>> = BTreeMap::new();
+    for entry in &ledger.entries {
+        left.entry((&entry.item, entry.kind))
+            .or_default()
+            .push(entry);
+    }
+    let mut entries = vec![None; reviewables.len()];
+    for (i, code) in reviewables.iter().enumerate() {
+        if let Some(candidates) = left.get_mut(&(code.item.as_str(), code.kind)) {
+            if let Some(at) = candidates.iter().position(|e| e.hash == code.hash) {
+                entries[i] = Some(candidates.remove(at));
+            }
+        }
+    }
+    for (i, code) in reviewables.iter().enumerate() {
+        if entries[i].is_none() {
+            if let Some(candidates) = left.get_mut(&(code.item.as_str(), code.kind)) {
+                if !candidates.is_empty() {
+                    entries[i] = Some(candidates.remove(0));
+                }
+            }
+        }
+    }
+    Matching {
+        entries,
+        orphans: left.into_values().flatten().collect(),
+    }
+}
+
+/// Checks every ledger in the workspace at `root`.
+pub fn check(root: &Path) -> Result<Vec<Finding>, String> {
+    let mut findings = Vec::new();
+    for tracked in tracked(root)? {
+        findings.extend(check_ledger(
+            &tracked.rel_path,
+            &tracked.text,
+            &tracked.ledger,
+            &tracked.reviewables,
+        ));
+    }
+    Ok(findings)
+}
+
+/// Checks one ledger against the code it covers.
+pub fn check_ledger(
+    rel_path: &str,
+    text: &str,
+    ledger: &Ledger,
+    reviewables: &[Reviewable],
+) -> Vec<Finding> {
+    let matching = match_entries(ledger, reviewables);
+    let mut findings = Vec::new();
+    for (code, entry) in reviewables.iter().zip(&matching.entries) {
+        let (rule, message) = match entry {
+            Some(entry) if entry.hash == code.hash => continue,
+            Some(entry) => (
+                Rule::StaleReview,
+                format!(
+                    "{} changed since {} reviewed it on {}",
+                    code.kind.describe(),
+                    entry.reviewer,
+                    entry.date
+                ),
+            ),
+            None => (
+                Rule::UnreviewedUnsafe,
+                format!("{} has no entry in {}", code.kind.describe(), rel_path),
+            ),
+        };
+        findings.push(Finding {
+            rule,
+            file: code.file.clone(),
+            line: code.line,
+            item: code.item.clone(),
+            message,
+            related: None,
+        });
+    }
+    for entry in matching.orphans {
+        // Point at the entry's hash, the line that identifies it
+        let line = text
+            .lines()
+            .position(|l| l.contains(&entry.hash))
+            .map_or(1, |i| i + 1);
+        findings.push(Finding {
+            rule: Rule::StaleReview,
+            file: rel_path.to_string(),
+            line,
+            item: entry.item.clone(),
+            message: format!(
+                "ledger entry reviewed by {} on {} matches no {} any more",
+                entry.reviewer,
+                entry.date,
+                entry.kind.describe()
+            ),
+            related: None,
+        });
+    }
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    findings
+}
+
+/// What `record` changed in the ledgers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recorded {
+    /// Code newly recorded as reviewed
+    pub stamped: Vec<Reviewable>,
+    /// Entries dropped because their code is gone
+    pub dropped: Vec<Entry>,
+}
+
+/// Records the new and changed code in `items` as reviewed by `reviewer`
+/// today, in every ledger of the workspace at `root`. Existing reviews are
+/// kept, entries for removed code are dropped, and code in other items is
+/// left as it was. Fails if an item has nothing to record.
+pub fn record(root: &Path, reviewer: &str, items: &[String]) -> Result<Recorded, String> {
+    let date = today();
+    let mut recorded = Recorded::default();
+    let mut updates = Vec::new();
+    for tracked in tracked(root)? {
+        let (ledger, changes) = record_ledger(
+            &tracked.ledger,
+            &tracked.reviewables,
+            reviewer,
+            &date,
+            items,
+        );
+        recorded.stamped.extend(changes.stamped);
+        recorded.dropped.extend(changes.dropped);
+        updates.push((tracked, ledger));
+    }
+    for item in items {
+        if !recorded.stamped.iter().any(|code| &code.item == item) {
+            return Err(format!("{}: no new or changed unsafe code to record", item));
+        }
+    }
+    for (tracked, ledger) in updates {
+        if ledger != tracked.ledger {
+            fs::write(&tracked.path, ledger.to_toml())
+                .map_err(|e| format!("{}: {}", tracked.rel_path, e))?;
+        }
+    }
+    Ok(recorded)
+}
+
+/// The ledger after recording the code of `items`, and what changed.
+fn record_ledger(
+    ledger: &Ledger,
+    reviewables: &[Reviewable],
+    reviewer: &str,
+    date: &str,
+    items: &[String],
+) -> (Ledger, Recorded) {
+    let matching = match_entries(ledger, reviewables);
+    let mut updated = Ledger::default();
+    let mut recorded = Recorded {
+        dropped: matching.orphans.into_iter().cloned().collect(),
+        ..Recorded::default()
+    };
+    for (code, entry) in reviewables.iter().zip(&matching.entries) {
+        match entry {
+            Some(entry) if entry.hash == code.hash => updated.entries.push((*entry).clone()),
+            _ if items.contains(&code.item) => {
+                updated.entries.push(Entry {
+                    item: code.item.clone(),
+                    kind: code.kind,
+                    hash: code.hash.clone(),
+                    reviewer: reviewer.to_string(),
+                    date: date.to_string(),
+                    rationale: code.comment.clone().unwrap_or_default(),
+                });
+                recorded.stamped.push(code.clone());
+            }
+            // Still stale: keep the old review for the check to report
+            Some(entry) => updated.entries.push((*entry).clone()),
+            None => {}
+        }
+    }
+    (updated, recorded)
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_date(secs / 86_400);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a Gregorian date (Howard Hinnant's
+/// `civil_from_days`).
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(item: &str, hash: &str, line: usize) -> Reviewable {
+        Reviewable {
+            item: item.to_string(),
+            kind: ReviewedKind::Block,
+            hash: hash.to_string(),
+            file: "src/lib.rs".to_string(),
+            line,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_stale_and_unreviewed() {
+        let text = r#"
+[[entry]]
+item = "krate::get"
+kind = "block"
+hash = "aaaa"
+reviewer = "jdoe"
+date = "2026-01-02"
+rationale = "in bounds"
+
+[[entry]]
+item = "krate::set"
+kind = "block"
+hash = "bbbb"
+reviewer = "jdoe"
+date = "2026-01-02"
+rationale = "in bounds"
+
+[[entry]]
+item = "krate::gone"
+kind = "block"
+hash = "cccc"
+reviewer = "jdoe"
+date = "2026-01-02"
+rationale = "in bounds"
+"#;
+        let ledger = Ledger::parse(text).unwrap();
+        let code = [
+            code("krate::get", "aaaa", 10),
+            code("krate::set", "bbbx", 20),
+            code("krate::fresh", "dddd", 30),
+        ];
+        let found: Vec<(Rule, String, usize)> =
+            check_ledger("safety-ledger.toml", text, &ledger, &code)
+                .into_iter()
+                .map(|f| (f.rule, f.item, f.line))
+                .collect();
+        assert_eq!(
+            found,
+            [
+                (Rule::StaleReview, "krate::gone".to_string(), 21),
+                (Rule::StaleReview, "krate::set".to_string(), 20),
+                (Rule::UnreviewedUnsafe, "krate::fresh".to_string(), 30),
+            ]
+        );
+        assert_eq!(Ledger::parse(&ledger.to_toml()).unwrap(), ledger);
+    }
+
+    #[test]
+    fn test_record_only_named_items() {
+        let reviewed = Entry {
+            item: "krate::get".to_string(),
+            kind: ReviewedKind::Block,
+            hash: "aaaa".to_string(),
+            reviewer: "jdoe".to_string(),
+            date: "2026-01-02".to_string(),
+            rationale: "in bounds".to_string(),
+        };
+        let stale = Entry {
+            item: "krate::set".to_string(),
+            hash: "bbbb".to_string(),
+            ..reviewed.clone()
+        };
+        let gone = Entry {
+            item: "krate::gone".to_string(),
+            ..reviewed.clone()
+        };
+        let ledger = Ledger {
+            entries: vec![reviewed.clone(), stale.clone(), gone.clone()],
+        };
+        let code = [
+            code("krate::get", "aaaa", 10),
+            code("krate::set", "bbbx", 20),
+            code("krate::fresh", "dddd", 30),
+            code("krate::other", "eeee", 40),
+        ];
+
+        let items = ["krate::fresh".to_string()];
+        let (updated, recorded) = record_ledger(&ledger, &code, "asmith", "2026-03-04", &items);
+        assert_eq!(recorded.stamped, [code[2].clone()]);
+        assert_eq!(recorded.dropped, [gone]);
+        let kept: Vec<(&str, &str)> = updated
+            .entries
+            .iter()
+            .map(|e| (e.item.as_str(), e.reviewer.as_str()))
+            .collect();
+        // The stale review stays stale, the other new block stays unreviewed
+        assert_eq!(
+            kept,
+            [
+                ("krate::get", "jdoe"),
+                ("krate::set", "jdoe"),
+                ("krate::fresh", "asmith")
+            ]
+        );
+        let findings = check_ledger("safety-ledger.toml", "", &updated, &code);
+        let rules: Vec<Rule> = findings.iter().map(|f| f.rule).collect();
+        assert_eq!(rules, [Rule::StaleReview, Rule::UnreviewedUnsafe]);
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(20_744), (2026, 10, 18));
+    }
+}
@@ -0,0 +1,35 @@
+//! Rust Memory Safety Auditor
+//!
+//! Audits the unsafe code of a Cargo workspace from syntax trees rather
+//! than text search: every `.rs` file is parsed with `syn`, so `unsafe` in
+//! comments, strings and doc examples never counts, and `unsafe impl`,
+//! `unsafe trait` and `extern` blocks are seen for what they are.
+//!
+//! Everything runs offline on sources alone. Only `migrate` compiles
+//! anything, to ask rustc where the unsafe operations are, and `geiger`
+//! asks cargo for the dependency graph.
+
+#![forbid(unsafe_code)]
+
+pub mod baseline;
+pub mod blocks;
+pub mod check;
+pub mod comments;
+pub mod count;
+pub mod diff;
+pub mod elf;
+pub mod embed;
+pub mod finding;
+pub mod geiger;
+pub mod git;
+pub mod graph;
+pub mod hash;
+pub mod items;
+pub mod leaks;
+pub mod ledger;
+pub mod manifest;
+pub mod mark;
+pub mod migrate;
+pub mod policy;
+pub mod sarif;
+pub mod workspace;
@@ -0,0 +1,401 @@
+//! `memory_audit` command line.
+//!
+//! ```text
+//! memory_audit count [--json] [--by item|module] [ROOT]
+//! memory_audit check [--json] [ROOT]
+//! memory_audit baseline [--update-baseline] [--json] [ROOT]
+//! memory_audit graph [--json] [ROOT]
+//! memory_audit leaks [--json] [ROOT]
+//! memory_audit policy [--json] [ROOT]
+//! memory_audit sarif [ROOT]
+//! memory_audit embedded [--json] FILE
+//! memory_audit geiger [--json] [ROOT]
+//! memory_audit ledger [--record REVIEWER --item PATH...] [--json] [ROOT]
+//! memory_audit mark [--dry-run] [--json] [ROOT]
+//! memory_audit migrate [--dry-run] [ROOT]
+//! memory_audit manifest [ROOT]
+//! memory_audit transitions --from REV [--to REV] [--json] [ROOT]
+//! ```
+//!
+//! `ROOT` is a workspace or package directory and defaults to the current
+//! directory. Checks exit with status 1 when they find problems, and every
+//! subcommand exits with status 2 on bad usage or unreadable sources.
+//! `transitions` compares the workspace at git revision `--from` with
+//! revision `--to`, or with the working tree when `--to` is left out.
+//! `sarif` runs every check and reports all findings as one SARIF 2.1.0
+//! log; it leaves failing to the dashboard and exits with status 0.
+//! `ledger --record` stamps only the items named with `--item`, one flag
+//! per item, and prints every entry it wrote.
+
+#![forbid(unsafe_code)]
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use memory_audit::count::{self, UnsafeCounts};
+use memory_audit::finding::Finding;
+use memory_audit::manifest::{self, TransitionReport};
+use memory_audit::{
+    baseline, check, elf, embed, geiger, graph, leaks, ledger, mark, migrate, policy, sarif,
+    workspace,
+};
+
+const USAGE: &str = "\
+usage: memory_audit count [--json] [--by item|module] [ROOT]
+       memory_audit check [--json] [ROOT]
+       memory_audit baseline [--update-baseline] [--json] [ROOT]
+       memory_audit graph [--json] [ROOT]
+       memory_audit leaks [--json] [ROOT]
+       memory_audit policy [--json] [ROOT]
+       memory_audit sarif [ROOT]
+       memory_audit embedded [--json] FILE
+       memory_audit geiger [--json] [ROOT]
+       memory_audit ledger [--record REVIEWER --item PATH...] [--json] [ROOT]
+       memory_audit mark [--dry-run] [--json] [ROOT]
+       memory_audit migrate [--dry-run] [ROOT]
+       memory_audit manifest [ROOT]
+       memory_audit transitions --from REV [--to REV] [--json] [ROOT]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("count") => run_count(&args[1..]),
+        Some("check") => run_check(&args[1..]),
+        Some("baseline") => run_baseline(&args[1..]),
+        Some("graph") => run_graph(&args[1..]),
+        Some("leaks") => run_leaks(&args[1..]),
+        Some("policy") => run_policy(&args[1..]),
+        Some("sarif") => run_sarif(&args[1..]),
+        Some("embedded") => run_embedded(&args[1..]),
+        Some("geiger") => run_geiger(&args[1..]),
+        Some("ledger") => run_ledger(&args[1..]),
+        Some("mark") => run_mark(&args[1..]),
+        Some("migrate") => run_migrate(&args[1..]),
+        Some("manifest") => run_manifest(&args[1..]),
+        Some("transitions") => run_transitions(&args[1..]),
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    result.unwrap_or_else(|message| {
+        eprintln!("memory_audit: {}", message);
+        ExitCode::from(2)
+    })
+}
+
+/// Options shared by every subcommand.
+struct Options {
+    json: bool,
+    dry_run: bool,
+    update_baseline: bool,
+    by: String,
+    from: Option<String>,
+    to: Option<String>,
+    record: Option<String>,
+    items: Vec<String>,
+    root: PathBuf,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        json: false,
+        dry_run: false,
+        update_baseline: false,
+        by: "module".to_string(),
+        from: None,
+        to: None,
+        record: None,
+        items: Vec::new(),
+        root: PathBuf::from("."),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--dry-run" => options.dry_run = true,
+            "--update-baseline" => options.update_baseline = true,
+            "--by" => {
+                options.by = args.next().ok_or("--by needs a value")?.clone();
+            }
+            "--from" => {
+                options.from = Some(args.next().ok_or("--from needs a revision")?.clone());
+            }
+            "--record" => {
+                options.record = Some(args.next().ok_or("--record needs a reviewer")?.clone());
+            }
+            "--item" => {
+                options
+                    .items
+                    .push(args.next().ok_or("--item needs a path")?.clone());
+            }
+            "--to" => {
+                options.to = Some(args.next().ok_or("--to needs a revision")?.clone());
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown flag {}", flag)),
+            root => options.root = PathBuf::from(root),
+        }
+    }
+    Ok(options)
+}
+
+fn run_count(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let report = count::count(&files)?;
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        println!("{}", json);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    match options.by.as_str() {
+        "item" => {
+            print_header("Item");
+            for item in &report.items {
+                let name = format!("{} ({}:{})", item.path, item.file, item.line);
+                print_row(&item.counts, &name);
+            }
+        }
+        "module" => {
+            print_header("Module");
+            for module in report.modules.iter().filter(|m| !m.counts.is_zero()) {
+                print_row(&module.counts, &module.path);
+            }
+        }
+        other => return Err(format!("--by must be item or module, not {}", other)),
+    }
+    print_row(&report.total, "TOTAL");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_check(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let findings = check::check(&files)?;
+    print_findings(&findings, options.json)
+}
+
+/// Fails when unsafe counts grew past their baselines, or with
+/// `--update-baseline` records the current counts.
+fn run_baseline(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    if options.update_baseline {
+        let changed = baseline::update(&options.root)?;
+        println!("{} baseline(s) updated", changed);
+        return Ok(ExitCode::SUCCESS);
+    }
+    let ratchet = baseline::check(&options.root)?;
+    let status = print_findings(&ratchet.findings, options.json)?;
+    if !options.json && ratchet.shrunk > 0 {
+        println!(
+            "{} count(s) are below their baseline; lock them in with --update-baseline",
+            ratchet.shrunk
+        );
+    }
+    Ok(status)
+}
+
+fn run_leaks(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let findings = leaks::leaks(&files)?;
+    print_findings(&findings, options.json)
+}
+
+/// Checks the workspace against its `safety-policy.toml`.
+fn run_policy(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let findings = policy::check(&options.root)?;
+    print_findings(&findings, options.json)
+}
+
+/// Prints the findings of every check as one SARIF log.
+fn run_sarif(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let findings = sarif::findings(&options.root)?;
+    let log = sarif::log(&options.root, &findings);
+    let json = serde_json::to_string_pretty(&log).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the safety summaries embedded in a built rlib or binary.
+fn run_embedded(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let file = &options.root;
+    let data = std::fs::read(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let sections = elf::find_sections(&data, embed::SECTION)
+        .map_err(|e| format!("{}: {}", file.display(), e))?;
+    let summaries: Vec<_> = sections.into_iter().flat_map(embed::decode_all).collect();
+    if summaries.is_empty() {
+        return Err(format!(
+            "{}: no {} section with a safety summary",
+            file.display(),
+            embed::SECTION
+        ));
+    }
+    if options.json {
+        let json = serde_json::to_string_pretty(&summaries).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        for summary in &summaries {
+            print!("{}", summary);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Unsafe metrics for every package in the dependency tree.
+fn run_geiger(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let report = geiger::geiger(&options.root)?;
+    if options.json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        print!("{}", report.to_table());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Checks the safety ledgers, or with `--record` brings them up to date.
+fn run_ledger(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    if let Some(reviewer) = &options.record {
+        if options.items.is_empty() {
+            return Err("--record needs the reviewed items, as --item PATH".to_string());
+        }
+        let recorded = ledger::record(&options.root, reviewer, &options.items)?;
+        for code in &recorded.stamped {
+            println!(
+                "{}:{}: {} in {} recorded as reviewed by {}",
+                code.file,
+                code.line,
+                code.kind.describe(),
+                code.item,
+                reviewer
+            );
+        }
+        for entry in &recorded.dropped {
+            println!(
+                "dropped entry for {}, whose code is gone (reviewed by {} on {})",
+                entry.item, entry.reviewer, entry.date
+            );
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let findings = ledger::check(&options.root)?;
+    print_findings(&findings, options.json)
+}
+
+/// Inserts TODO(MS-audit) markers, or with `--dry-run` only reports them.
+fn run_mark(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let report = mark::mark(&files, options.dry_run)?;
+    if options.json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        print!("{}", report);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Wraps unsafe operations in `unsafe fn` bodies in their own blocks, or
+/// with `--dry-run` prints the change as a diff.
+fn run_migrate(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let ops = migrate::unsafe_ops(&options.root)?;
+    let migration = migrate::plan(&options.root, &files, &ops)?;
+    if options.dry_run {
+        print!("{}", migration.diff());
+    } else {
+        migration.write()?;
+    }
+    print!("{}", migration);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the safety manifest of the working tree as JSON.
+fn run_manifest(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let manifest = manifest::manifest(&files)?;
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Reports safety transitions between two revisions.
+fn run_transitions(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let from = options.from.ok_or("transitions needs --from REV")?;
+    let (from_commit, old) = manifest::at_revision(&options.root, &from)?;
+    let (to, new) = match &options.to {
+        Some(rev) => manifest::at_revision(&options.root, rev)?,
+        None => {
+            let files = workspace::source_files(&options.root)?;
+            ("working tree".to_string(), manifest::manifest(&files)?)
+        }
+    };
+    let short = |rev: String| rev.get(..12).map_or(rev.clone(), str::to_string);
+    let report = TransitionReport::new(short(from_commit), &old, short(to), &new);
+    if options.json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        print!("{}", report);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the propagation graph as DOT, or as JSON with `--json`.
+fn run_graph(args: &[String]) -> Result<ExitCode, String> {
+    let options = parse_options(args)?;
+    let files = workspace::source_files(&options.root)?;
+    let graph = graph::build(&files)?;
+    if options.json {
+        let json = serde_json::to_string_pretty(&graph).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        print!("{}", graph.to_dot());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints findings, one per line or as JSON, and picks the exit status.
+fn print_findings(findings: &[Finding], json: bool) -> Result<ExitCode, String> {
+    if json {
+        let json = serde_json::to_string_pretty(findings).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        for finding in findings {
+            println!("{}", finding);
+        }
+        println!("{} finding(s)", findings.len());
+    }
+    Ok(if findings.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn print_header(what: &str) {
+    println!(
+        "{:>7} {:>7} {:>7} {:>7} {:>7}  {}",
+        "Blocks", "Fns", "Impls", "Traits", "Externs", what
+    );
+}
+
+fn print_row(counts: &UnsafeCounts, name: &str) {
+    println!(
+        "{:>7} {:>7} {:>7} {:>7} {:>7}  {}",
+        counts.blocks, counts.functions, counts.impls, counts.traits, counts.extern_blocks, name
+    );
+}
@@ -0,0 +1,488 @@
+//! Safety manifests, and the transitions between two of them.
+//!
+//! A manifest records, for every function and every `unsafe impl`, how it
+//! handles unsafety and hashes of what a reviewer signed off on: the body
+//! of each `unsafe { }` block and the `// SAFETY` comment on it. Comparing
+//! the manifests of two revisions shows the changes a safety review has to
+//! look at, however large the rest of the diff is:
+//!
+//! | Transition                 | Meaning                                           |
+//! |----------------------------|---------------------------------------------------|
+//! | `new-unsafe-fn`            | an `unsafe fn` that was not one before            |
+//! | `suppressor-to-propagator` | a safe fn with `unsafe { }` became an `unsafe fn` |
+//! | `discharge-deleted`        | a block or impl lost its `// SAFETY` comment      |
+//! | `block-changed`            | an `unsafe { }` body changed under its comment    |
+//!
+//! Items are matched by path, and blocks within an item first by body, then
+//! in order, so moving code around a file is not a transition.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::blocks;
+use crate::comments;
+use crate::git;
+use crate::hash::{comment_hash, hash};
+use crate::items::{self, ItemKind, Syntax};
+use crate::workspace::{self, SourceFile};
+
+/// Marker prefix shared by `// SAFETY:` and `// SAFETY DISCHARGE:`.
+const SAFETY: &str = "SAFETY";
+
+/// How an item handles unsafety.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Class {
+    Safe,
+    UnsafeFn,
+    /// Safe fn, static or const containing `unsafe { }`
+    Suppressor,
+    UnsafeImpl,
+}
+
+impl Class {
+    pub const ALL: [Class; 4] = [
+        Class::Safe,
+        Class::UnsafeFn,
+        Class::Suppressor,
+        Class::UnsafeImpl,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Class::Safe => "safe",
+            Class::UnsafeFn => "unsafe fn",
+            Class::Suppressor => "suppressor",
+            Class::UnsafeImpl => "unsafe impl",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BlockEntry {
+    pub line: usize,
+    /// Hash of the block's tokens, so formatting changes don't count
+    pub body: String,
+    /// Hash of its `// SAFETY` comment
+    pub discharge: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub path: String,
+    pub kind: ItemKind,
+    pub class: Class,
+    pub file: String,
+    pub line: usize,
+    /// Hash of the `// SAFETY:` comment of an `unsafe impl`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<String>,
+    pub blocks: Vec<BlockEntry>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Manifest {
+    /// Sorted by path
+    pub items: Vec<Entry>,
+}
+
+impl Manifest {
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        self.items
+            .binary_search_by(|e| e.path.as_str().cmp(path))
+            .ok()
+            .map(|i| &self.items[i])
+    }
+
+    /// Number of items in each class.
+    pub fn counts(&self) -> BTreeMap<Class, usize> {
+        let mut counts: BTreeMap<Class, usize> = Class::ALL.iter().map(|&c| (c, 0)).collect();
+        for entry in &self.items {
+            *counts.entry(entry.class).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// Builds the manifest of every source file.
+pub fn manifest(files: &[SourceFile]) -> Result<Manifest, String> {
+    let mut items = Vec::new();
+    for file in files {
+        let (text, syntax) = file.parse_with_text()?;
+        manifest_file(
+            &file.rel_path,
+            &file.module_path,
+            &text,
+            &syntax,
+            &mut items,
+        );
+    }
+    // cfg'd alternatives can share a path; number them to keep keys unique
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in &mut items {
+        let n = seen.entry(entry.path.clone()).or_default();
+        *n += 1;
+        if *n > 1 {
+            entry.path = format!("{}#{}", entry.path, n);
+        }
+    }
+    items.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Manifest { items })
+}
+
+/// Builds the manifest of the workspace at `root` as of git revision
+/// `rev`, exported to a scratch directory. Returns the commit id too.
+pub fn at_revision(root: &Path, rev: &str) -> Result<(String, Manifest), String> {
+    let commit = git::resolve(root, rev)?;
+    let scratch = std::env::temp_dir().join(format!(
+        "memory_audit-{}-{}",
+        std::process::id(),
+        &commit[..12]
+    ));
+    let _ = fs::remove_dir_all(&scratch);
+    let result = git::export(root, &commit, &scratch)
+        .and_then(|()| workspace::source_files(&scratch))
+        .and_then(|files| manifest(&files));
+    let _ = fs::remove_dir_all(&scratch);
+    result.map(|manifest| (commit, manifest))
+}
+
+/// Adds the entries of one parsed file to `out`.
+pub fn manifest_file(
+    rel_path: &str,
+    module_path: &[String],
+    text: &str,
+    syntax: &syn::File,
+    out: &mut Vec<Entry>,
+) {
+    let lines: Vec<&str> = text.lines().collect();
+    items::walk(syntax, module_path, &mut |unit| {
+        let blocks: Vec<BlockEntry> = blocks::unsafe_blocks(&unit)
+            .into_iter()
+            .map(|block| BlockEntry {
+                line: block.line,
+                body: hash(&block.body),
+                discharge: comments::find_marked(&lines, block.line, SAFETY)
+                    .or_else(|| comments::find_marked(&lines, block.stmt_line, SAFETY))
+                    .map(|comment| comment_hash(&comment)),
+            })
+            .collect();
+        let class = match (unit.kind, &unit.syntax) {
+            (ItemKind::Impl, _) if unit.is_unsafe => Class::UnsafeImpl,
+            (_, Syntax::Fn { .. }) if unit.is_unsafe => Class::UnsafeFn,
+            (_, Syntax::Fn { .. } | Syntax::Init { .. }) if !blocks.is_empty() => Class::Suppressor,
+            (_, Syntax::Fn { .. }) => Class::Safe,
+            _ => return,
+        };
+        let rationale = (class == Class::UnsafeImpl)
+            .then(|| comments::find_marked(&lines, unit.start_line, comments::RATIONALE))
+            .flatten()
+            .map(|comment| comment_hash(&comment));
+        out.push(Entry {
+            path: unit.path.clone(),
+            kind: unit.kind,
+            class,
+            file: rel_path.to_string(),
+            line: unit.line,
+            rationale,
+            blocks,
+        });
+    });
+}
+
+/// What changed between two manifests. Ids are stable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TransitionKind {
+    NewUnsafeFn,
+    SuppressorToPropagator,
+    DischargeDeleted,
+    BlockChanged,
+}
+
+impl TransitionKind {
+    pub fn id(self) -> &'static str {
+        match self {
+            TransitionKind::NewUnsafeFn => "new-unsafe-fn",
+            TransitionKind::SuppressorToPropagator => "suppressor-to-propagator",
+            TransitionKind::DischargeDeleted => "discharge-deleted",
+            TransitionKind::BlockChanged => "block-changed",
+        }
+    }
+}
+
+impl Serialize for TransitionKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+/// A change between two manifests; the location is in the newer one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub file: String,
+    pub line: usize,
+    pub item: String,
+    pub message: String,
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {} (in {})",
+            self.file,
+            self.line,
+            self.kind.id(),
+            self.message,
+            self.item
+        )
+    }
+}
+
+/// Lists the transitions from `old` to `new`, sorted by file and line.
+pub fn transitions(old: &Manifest, new: &Manifest) -> Vec<Transition> {
+    let mut out = Vec::new();
+    for entry in &new.items {
+        let mut report = |kind, line, message: &str| {
+            out.push(Transition {
+                kind,
+                file: entry.file.clone(),
+                line,
+                item: entry.path.clone(),
+                message: message.to_string(),
+            });
+        };
+        let before = old.get(&entry.path);
+        match (before.map(|e| e.class), entry.class) {
+            (Some(Class::Suppressor), Class::UnsafeFn) => report(
+                TransitionKind::SuppressorToPropagator,
+                entry.line,
+                "safe fn became an unsafe fn; its callers now carry the obligations",
+            ),
+            (Some(Class::Safe), Class::UnsafeFn) => report(
+                TransitionKind::NewUnsafeFn,
+                entry.line,
+                "safe fn became an unsafe fn",
+            ),
+            (None, Class::UnsafeFn) => {
+                report(TransitionKind::NewUnsafeFn, entry.line, "new unsafe fn")
+            }
+            _ => {}
+        }
+        let Some(before) = before else {
+            continue;
+        };
+
+        if before.rationale.is_some() && entry.rationale.is_none() {
+            report(
+                TransitionKind::DischargeDeleted,
+                entry.line,
+                "unsafe impl lost its `// SAFETY:` comment",
+            );
+        }
+        for (old_block, new_block) in match_blocks(&before.blocks, &entry.blocks) {
+            if old_block.discharge.is_some() && new_block.discharge.is_none() {
+                report(
+                    TransitionKind::DischargeDeleted,
+                    new_block.line,
+                    "unsafe block lost its `// SAFETY` comment",
+                );
+            }
+            if old_block.body != new_block.body {
+                let message = if new_block.discharge.is_some() {
+                    "unsafe block changed; check its `// SAFETY` comment still holds"
+                } else {
+                    "unsafe block changed"
+                };
+                report(TransitionKind::BlockChanged, new_block.line, message);
+            }
+        }
+    }
+    out.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    out
+}
+
+/// Pairs the blocks of an item's two versions: equal bodies first, then
+/// what is left in order. Added and removed blocks stay unpaired.
+fn match_blocks<'a>(
+    old: &'a [BlockEntry],
+    new: &'a [BlockEntry],
+) -> Vec<(&'a BlockEntry, &'a BlockEntry)> {
+    let mut old_left: Vec<&BlockEntry> = old.iter().collect();
+    let mut new_left = Vec::new();
+    let mut pairs = Vec::new();
+    for block in new {
+        match old_left.iter().position(|o| o.body == block.body) {
+            Some(i) => pairs.push((old_left.remove(i), block)),
+            None => new_left.push(block),
+        }
+    }
+    pairs.extend(old_left.into_iter().zip(new_left));
+    pairs
+}
+
+/// The transitions between two revisions, with class counts for context.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TransitionReport {
+    pub from: String,
+    pub to: String,
+    pub counts_from: BTreeMap<Class, usize>,
+    pub counts_to: BTreeMap<Class, usize>,
+    pub transitions: Vec<Transition>,
+}
+
+impl TransitionReport {
+    pub fn new(from: String, old: &Manifest, to: String, new: &Manifest) -> TransitionReport {
+        TransitionReport {
+            from,
+            to,
+            counts_from: old.counts(),
+            counts_to: new.counts(),
+            transitions: transitions(old, new),
+        }
+    }
+}
+
+impl fmt::Display for TransitionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Safety transitions {}..{}", self.from, self.to)?;
+        writeln!(f)?;
+        for class in Class::ALL {
+            writeln!(
+                f,
+                "  {:<12} {:>5} -> {:<5}",
+                class.as_str(),
+                self.counts_from.get(&class).unwrap_or(&0),
+                self.counts_to.get(&class).unwrap_or(&0)
+            )?;
+        }
+        writeln!(f)?;
+        for transition in &self.transitions {
+            writeln!(f, "{}", transition)?;
+        }
+        writeln!(f, "{} transition(s)", self.transitions.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest_of(source: &str) -> Manifest {
+        let syntax = syn::parse_file(source).unwrap();
+        let mut items = Vec::new();
+        manifest_file(
+            "src/lib.rs",
+            &["krate".to_string()],
+            source,
+            &syntax,
+            &mut items,
+        );
+        items.sort_by(|a, b| a.path.cmp(&b.path));
+        Manifest { items }
+    }
+
+    #[test]
+    fn test_classes() {
+        let manifest = manifest_of(
+            "
+            // SAFETY: no interior mutability
+            unsafe impl Send for Buf {}
+            impl Buf {}
+            fn safe() {}
+            unsafe fn raw() {}
+            fn wrapper() { unsafe { raw() } }
+            static S: u8 = unsafe { read() };
+            const C: u8 = 1;
+            ",
+        );
+        let classes: Vec<(&str, Class, bool)> = manifest
+            .items
+            .iter()
+            .map(|e| (e.path.as_str(), e.class, e.rationale.is_some()))
+            .collect();
+        assert_eq!(
+            classes,
+            [
+                ("krate::<Buf as Send>", Class::UnsafeImpl, true),
+                ("krate::S", Class::Suppressor, false),
+                ("krate::raw", Class::UnsafeFn, false),
+                ("krate::safe", Class::Safe, false),
+                ("krate::wrapper", Class::Suppressor, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transitions() {
+        let old = manifest_of(
+            "
+            fn alloc() -> u8 {
+                // SAFETY DISCHARGE: checked above
+                unsafe { raw(1) }
+            }
+            fn read() -> u8 {
+                // SAFETY DISCHARGE: in bounds
+                let a = unsafe { raw(2) };
+                // SAFETY DISCHARGE: in bounds
+                let b = unsafe { raw(3) };
+                a + b
+            }
+            fn moved() -> u8 {
+                // SAFETY DISCHARGE: fine
+                unsafe { raw(4) }
+            }
+            fn plain() {}
+            ",
+        );
+        let new = manifest_of(
+            "
+            fn plain() {}
+            unsafe fn alloc() -> u8 {
+                unsafe { raw(1) }
+            }
+            fn read() -> u8 {
+                // SAFETY DISCHARGE: in bounds
+                let b = unsafe { raw(3) };
+                // SAFETY DISCHARGE: in bounds
+                let a = unsafe { raw(2) + 1 };
+                a + b
+            }
+
+
+
+            fn moved() -> u8 {
+                // SAFETY DISCHARGE: fine
+                unsafe { raw(4) }
+            }
+            unsafe fn fresh() {}
+            ",
+        );
+        let found: Vec<(TransitionKind, String, usize)> = transitions(&old, &new)
+            .into_iter()
+            .map(|t| (t.kind, t.item, t.line))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    TransitionKind::SuppressorToPropagator,
+                    "krate::alloc".to_string(),
+                    3
+                ),
+                (
+                    TransitionKind::DischargeDeleted,
+                    "krate::alloc".to_string(),
+                    4
+                ),
+                (TransitionKind::BlockChanged, "krate::read".to_string(), 10),
+                (TransitionKind::NewUnsafeFn, "krate::fresh".to_string(), 20),
+            ]
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Marking code that needs a propagate-or-suppress decision.
+//!
+//! The migration counterpart of `check`: instead of failing on code that
+//! never made an explicit safety decision, it writes a structured marker
+//! above it, the Rust analogue of the `TODO (MSv2)` markers described in
+//! `csharp-migration.md`. Two shapes of code are marked:
+//!
+//! - An `unsafe { }` block in a safe fn with no `// SAFETY DISCHARGE:`
+//!   comment: the fn suppresses, but no one wrote down why that is sound
+//! - An `unsafe fn` with no inner `unsafe { }` blocks: its whole body is one
+//!   implicit unsafe context, so no operation was ever looked at on its own
+//!
+//! Markers are plain comments, so marked code compiles exactly as before.
+//! Marking is idempotent: code that already has a marker directly above it
+//! is left alone. Methods of trait impls are skipped, since the trait, not
+//! the impl, decides whether they are `unsafe`.
+
+use std::fmt;
+use std::fs;
+
+use serde::Serialize;
+
+use crate::blocks;
+use crate::comments::{self, DISCHARGE};
+use crate::items::{self, ItemKind};
+use crate::workspace::SourceFile;
+
+/// First line of every marker; also how existing markers are recognized.
+pub const MARKER: &str = "TODO(MS-audit): PROPAGATE or SUPPRESS?";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkKind {
+    /// `unsafe { }` in a safe fn without a discharge comment
+    UndischargedBlock,
+    /// `unsafe fn` without inner `unsafe { }` blocks
+    ImplicitUnsafeFn,
+}
+
+impl MarkKind {
+    pub fn describe(self) -> &'static str {
+        match self {
+            MarkKind::UndischargedBlock => "unsafe block in safe fn without discharge",
+            MarkKind::ImplicitUnsafeFn => "unsafe fn without inner unsafe blocks",
+        }
+    }
+}
+
+/// A marker, added or found already in place.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Mark {
+    pub kind: MarkKind,
+    pub file: String,
+    /// Line of the marked code, before any marker was inserted
+    pub line: usize,
+    pub item: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MarkReport {
+    /// Markers inserted by this run
+    pub added: Vec<Mark>,
+    /// Code that already carried a marker
+    pub existing: Vec<Mark>,
+    /// Whether the files were rewritten (false for a dry run)
+    pub written: bool,
+}
+
+/// Marks every source file, rewriting them unless `dry_run` is set.
+pub fn mark(files: &[SourceFile], dry_run: bool) -> Result<MarkReport, String> {
+    let mut report = MarkReport {
+        written: !dry_run,
+        ..MarkReport::default()
+    };
+    for file in files {
+        let (text, syntax) = file.parse_with_text()?;
+        let (marked, added, existing) =
+            mark_file(&file.rel_path, &file.module_path, &text, &syntax);
+        if !added.is_empty() && !dry_run {
+            fs::write(&file.path, marked).map_err(|e| format!("{}: {}", file.rel_path, e))?;
+        }
+        report.added.extend(added);
+        report.existing.extend(existing);
+    }
+    Ok(report)
+}
+
+/// Returns `text` with markers inserted, the markers added, and the code
+/// that was already marked.
+pub fn mark_file(
+    rel_path: &str,
+    module_path: &[String],
+    text: &str,
+    syntax: &syn::File,
+) -> (String, Vec<Mark>, Vec<Mark>) {
+    let lines: Vec<&str> = text.lines().collect();
+    // (line to insert above, mark)
+    let mut wanted: Vec<(usize, Mark)> = Vec::new();
+
+    items::walk(syntax, module_path, &mut |unit| {
+        if !unit.kind.is_fn() || (unit.kind == ItemKind::Method && unit.impl_trait.is_some()) {
+            return;
+        }
+        let mark = |kind, line| Mark {
+            kind,
+            file: rel_path.to_string(),
+            line,
+            item: unit.path.clone(),
+        };
+        let blocks = blocks::unsafe_blocks(&unit);
+        if unit.is_unsafe {
+            if blocks.is_empty() {
+                wanted.push((unit.start_line, mark(MarkKind::ImplicitUnsafeFn, unit.line)));
+            }
+        } else {
+            for block in blocks {
+                let discharged = comments::find_marked(&lines, block.line, DISCHARGE)
+                    .or_else(|| comments::find_marked(&lines, block.stmt_line, DISCHARGE))
+                    .is_some();
+                if !discharged {
+                    wanted.push((
+                        block.stmt_line,
+                        mark(MarkKind::UndischargedBlock, block.line),
+                    ));
+                }
+            }
+        }
+    });
+
+    // Several blocks in one statement share one marker
+    wanted.sort_by_key(|(at, _)| *at);
+    wanted.dedup_by_key(|(at, _)| *at);
+
+    let mut added = Vec::new();
+    let mut existing = Vec::new();
+    let mut inserts = Vec::new();
+    for (at, mark) in wanted {
+        if comments::find_marked(&lines, at, MARKER).is_some() {
+            existing.push(mark);
+        } else {
+            inserts.push((at, marker_text(&mark, indent(lines[at - 1]))));
+            added.push(mark);
+        }
+    }
+
+    let mut out = String::with_capacity(text.len() + inserts.len() * 256);
+    let mut inserts = inserts.into_iter().peekable();
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        while let Some((_, marker)) = inserts.next_if(|(at, _)| *at == i + 1) {
+            out.push_str(&marker);
+        }
+        out.push_str(line);
+    }
+    (out, added, existing)
+}
+
+fn indent(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn marker_text(mark: &Mark, indent: &str) -> String {
+    let name = mark.item.rsplit("::").next().unwrap_or(&mark.item);
+    let body = match mark.kind {
+        MarkKind::UndischargedBlock => [
+            format!(
+                "This unsafe block in safe fn `{}` has no SAFETY DISCHARGE",
+                name
+            ),
+            "comment, so no one recorded why it is sound. Decision needed:".to_string(),
+            "  - PROPAGATE: make the fn `unsafe fn` and document `# Safety`".to_string(),
+            "  - SUPPRESS: keep it safe and add `// SAFETY DISCHARGE:` here".to_string(),
+        ],
+        MarkKind::ImplicitUnsafeFn => [
+            format!(
+                "`{}` is an unsafe fn without inner unsafe blocks, so its",
+                name
+            ),
+            "whole body is one unreviewed unsafe context. Decision needed:".to_string(),
+            "  - PROPAGATE: keep `unsafe fn`, wrap each unsafe operation in a block".to_string(),
+            "  - SUPPRESS: make it safe and discharge its obligations inside".to_string(),
+        ],
+    };
+    let mut text = format!("{}// {}\n", indent, MARKER);
+    for line in body {
+        text.push_str(&format!("{}// {}\n", indent, line));
+    }
+    text
+}
+
+impl fmt::Display for MarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |kind| self.added.iter().filter(|m| m.kind == kind).count();
+        writeln!(f, "Memory Safety Audit Markers")?;
+        writeln!(f, "===========================")?;
+        writeln!(
+            f,
+            "Markers {}: {:>5}",
+            if self.written { "added" } else { "to add" },
+            self.added.len()
+        )?;
+        for kind in [MarkKind::UndischargedBlock, MarkKind::ImplicitUnsafeFn] {
+            writeln!(f, "  - {}: {}", kind.describe(), count(kind))?;
+        }
+        writeln!(f, "Already marked: {:>5}", self.existing.len())?;
+        if !self.added.is_empty() {
+            writeln!(f)?;
+            for mark in &self.added {
+                writeln!(f, "  {}:{}  {}", mark.file, mark.line, mark.item)?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Next steps:")?;
+        writeln!(f, "  1. Review each TODO(MS-audit) marker")?;
+        writeln!(f, "  2. Decide: PROPAGATE or SUPPRESS")?;
+        writeln!(
+            f,
+            "  3. Write the SAFETY DISCHARGE or # Safety docs, then remove the marker"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str) -> (String, usize, usize) {
+        let syntax = syn::parse_file(text).unwrap();
+        let (out, added, existing) = mark_file("lib.rs", &["krate".to_string()], text, &syntax);
+        (out, added.len(), existing.len())
+    }
+
+    #[test]
+    fn test_marks_and_is_idempotent() {
+        let source = "\
+/// Docs
+unsafe fn raw(p: *mut i32) -> i32 {
+    *p
+}
+
+fn wrapper(p: *mut i32) -> i32 {
+    let a = unsafe { raw(p) } + unsafe { raw(p) };
+    // SAFETY DISCHARGE: fine
+    a + unsafe { raw(p) }
+}
+";
+        let (once, added, existing) = run(source);
+        assert_eq!((added, existing), (2, 0));
+        assert!(once.starts_with(&format!("// {}\n", MARKER)));
+        assert!(once.contains(&format!("    // {}\n    // This unsafe block", MARKER)));
+        assert!(once.contains("/// Docs\nunsafe fn raw"));
+
+        let (twice, added, existing) = run(&once);
+        assert_eq!((added, existing), (0, 2));
+        assert_eq!(twice, once);
+    }
+}
@@ -0,0 +1,531 @@
+//! Migrating to `#![deny(unsafe_op_in_unsafe_fn)]`.
+//!
+//! Under RFC 2585 the body of an `unsafe fn` is no longer one big unsafe
+//! context: each unsafe operation needs its own `unsafe { }` block, so each
+//! can carry its own justification. The migration:
+//!
+//! 1. Asks the compiler where the operations are: `cargo check` with the
+//!    lint turned on reports every unsafe operation in an `unsafe fn` body
+//!    outside an inner block. This is exact where a syntax-only search would
+//!    have to guess whether `*p` dereferences a raw pointer.
+//! 2. Wraps each statement holding such an operation in `unsafe { }`, with a
+//!    placeholder `// SAFETY:` comment above it for a reviewer to replace.
+//!    Control flow is wrapped piecewise (the condition of an `if`, each arm
+//!    of a `match`), so one block never hides several decisions.
+//! 3. Adds `#![deny(unsafe_op_in_unsafe_fn)]` to the root of every crate
+//!    that has an `unsafe fn` with a body, so the result stays migrated.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use proc_macro2::{Span, TokenTree};
+use quote::ToTokens;
+use serde::Serialize;
+use syn::spanned::Spanned;
+use syn::{Block, Expr, Stmt};
+
+use crate::diff;
+use crate::items::{self, Syntax};
+use crate::workspace::SourceFile;
+
+pub const LINT: &str = "unsafe_op_in_unsafe_fn";
+
+/// Comment put above every block the migration adds.
+pub const PLACEHOLDER: &str = "SAFETY: TODO(MS-audit) explain why this is sound";
+
+/// A position as `proc_macro2` reports it: 1-based line, 0-based column
+/// in characters.
+pub type Pos = (usize, usize);
+
+/// An unsafe operation the compiler found outside an inner unsafe block.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct UnsafeOp {
+    pub file: String,
+    pub start: Pos,
+    pub end: Pos,
+    pub message: String,
+}
+
+/// A statement (or part of one) wrapped in a new unsafe block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Wrapped {
+    pub file: String,
+    pub line: usize,
+    /// The `unsafe fn` it is in
+    pub item: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChange {
+    pub rel_path: String,
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Migration {
+    pub changes: Vec<FileChange>,
+    pub wrapped: Vec<Wrapped>,
+    /// Crate roots the deny attribute was added to
+    pub roots: Vec<String>,
+}
+
+/// Runs `cargo check` on the workspace at `root` with the lint enabled and
+/// collects the operations it reports.
+///
+/// Builds into `target/memory_audit-migrate`, so the changed `RUSTFLAGS`
+/// don't invalidate the normal build.
+pub fn unsafe_ops(root: &Path) -> Result<Vec<UnsafeOp>, String> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let rustflags = format!(
+        "{} -W{}",
+        std::env::var("RUSTFLAGS").unwrap_or_default(),
+        LINT
+    );
+    let output = Command::new(cargo)
+        .args(["check", "--workspace", "--all-targets", "--all-features"])
+        .args(["--offline", "--message-format=json"])
+        .current_dir(root)
+        .env("RUSTFLAGS", rustflags.trim())
+        .env("CARGO_TARGET_DIR", root.join("target/memory_audit-migrate"))
+        .output()
+        .map_err(|e| format!("failed to run cargo: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo check failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(parse_messages(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Picks the lint's primary spans out of cargo's JSON messages.
+fn parse_messages(stdout: &str) -> Vec<UnsafeOp> {
+    let mut ops = BTreeSet::new();
+    for line in stdout.lines() {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        // The lint reuses E0133, the hard error for unsafe operations
+        // outside any unsafe context; only the lint is a warning
+        let message = &message["message"];
+        if message["code"]["code"] != "E0133" || message["level"] != "warning" {
+            continue;
+        }
+        let spans = message["spans"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for span in spans.iter().filter(|s| s["is_primary"] == true) {
+            let number = |key: &str| span[key].as_u64().unwrap_or(0) as usize;
+            ops.insert(UnsafeOp {
+                file: span["file_name"].as_str().unwrap_or_default().to_string(),
+                // The compiler's columns are 1-based
+                start: (
+                    number("line_start"),
+                    number("column_start").saturating_sub(1),
+                ),
+                end: (number("line_end"), number("column_end").saturating_sub(1)),
+                message: message["message"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+    }
+    ops.into_iter().collect()
+}
+
+/// Plans the rewrite of `files` for the operations in `ops`. `root` is the
+/// directory cargo ran in, which the operations' paths are relative to.
+pub fn plan(root: &Path, files: &[SourceFile], ops: &[UnsafeOp]) -> Result<Migration, String> {
+    let mut parsed = Vec::new();
+    let mut unsafe_crates = BTreeSet::new();
+    for file in files {
+        let (text, syntax) = file.parse_with_text()?;
+        let mut has_unsafe_fn = false;
+        items::walk(&syntax, &file.module_path, &mut |unit| {
+            has_unsafe_fn |= unit.kind.is_fn() && unit.is_unsafe;
+        });
+        if has_unsafe_fn {
+            unsafe_crates.insert(crate_key(file));
+        }
+        parsed.push((file, text, syntax));
+    }
+
+    let mut migration = Migration::default();
+    for (file, text, syntax) in parsed {
+        let spans: Vec<(Pos, Pos)> = ops
+            .iter()
+            .filter(|op| same_file(&root.join(&op.file), &file.path))
+            .map(|op| (op.start, op.end))
+            .collect();
+        let deny = file.is_crate_root() && unsafe_crates.contains(&crate_key(file));
+        let (after, wrapped) = rewrite_file(
+            &file.rel_path,
+            &file.module_path,
+            &text,
+            &syntax,
+            &spans,
+            deny,
+        );
+        if after != text {
+            if deny && !has_deny(&syntax) {
+                migration.roots.push(file.rel_path.clone());
+            }
+            migration.wrapped.extend(wrapped);
+            migration.changes.push(FileChange {
+                rel_path: file.rel_path.clone(),
+                path: file.path.clone(),
+                before: text,
+                after,
+            });
+        }
+    }
+    Ok(migration)
+}
+
+fn crate_key(file: &SourceFile) -> (String, String) {
+    (file.package.clone(), file.module_path[0].clone())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+impl Migration {
+    /// Writes every changed file.
+    pub fn write(&self) -> Result<(), String> {
+        for change in &self.changes {
+            fs::write(&change.path, &change.after)
+                .map_err(|e| format!("{}: {}", change.rel_path, e))?;
+        }
+        Ok(())
+    }
+
+    /// The whole migration as a unified diff.
+    pub fn diff(&self) -> String {
+        self.changes
+            .iter()
+            .map(|c| diff::unified(&c.rel_path, &c.before, &c.after))
+            .collect()
+    }
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fns: BTreeSet<&str> = self.wrapped.iter().map(|w| w.item.as_str()).collect();
+        writeln!(
+            f,
+            "{} unsafe block(s) added in {} unsafe fn(s), {} file(s) changed",
+            self.wrapped.len(),
+            fns.len(),
+            self.changes.len()
+        )?;
+        for wrapped in &self.wrapped {
+            writeln!(f, "  {}:{}  {}", wrapped.file, wrapped.line, wrapped.item)?;
+        }
+        for root in &self.roots {
+            writeln!(f, "#![deny({})] added to {}", LINT, root)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites one file: wraps the statements holding `ops` (compiler spans
+/// in this file) and, if `deny` is set, adds the lint to the crate root.
+pub fn rewrite_file(
+    rel_path: &str,
+    module_path: &[String],
+    text: &str,
+    syntax: &syn::File,
+    ops: &[(Pos, Pos)],
+    deny: bool,
+) -> (String, Vec<Wrapped>) {
+    let mut wrapper = Wrapper {
+        ops,
+        spans: Vec::new(),
+    };
+    let mut wrapped = Vec::new();
+    items::walk(syntax, module_path, &mut |unit| {
+        if let (true, Syntax::Fn { block, .. }) = (unit.is_unsafe, unit.syntax) {
+            let before = wrapper.spans.len();
+            wrapper.block(block);
+            for &(start, _) in &wrapper.spans[before..] {
+                wrapped.push(Wrapped {
+                    file: rel_path.to_string(),
+                    line: start.0,
+                    item: unit.path.clone(),
+                });
+            }
+        }
+    });
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut inserts: Vec<(usize, String)> = Vec::new();
+    let mut commented = BTreeSet::new();
+    for &(start, end) in &wrapper.spans {
+        if commented.insert(start.0) {
+            let line = lines[start.0 - 1];
+            let indent = &line[..line.len() - line.trim_start().len()];
+            inserts.push((
+                offset(text, (start.0, 0)),
+                format!("{}// {}\n", indent, PLACEHOLDER),
+            ));
+        }
+        inserts.push((offset(text, start), "unsafe { ".to_string()));
+        inserts.push((offset(text, end), " }".to_string()));
+    }
+    if deny && !has_deny(syntax) {
+        inserts.push(deny_insert(text, syntax));
+    }
+
+    // Apply back to front so earlier offsets stay valid; at equal offsets
+    // keep the order the inserts were made in
+    let mut out = text.to_string();
+    inserts.sort_by_key(|(at, _)| *at);
+    for (at, insert) in inserts.iter().rev() {
+        out.insert_str(*at, insert);
+    }
+    (out, wrapped)
+}
+
+fn has_deny(syntax: &syn::File) -> bool {
+    syntax.attrs.iter().any(|attr| {
+        (attr.path().is_ident("deny") || attr.path().is_ident("forbid"))
+            && items::tokens(&attr.meta).contains(LINT)
+    })
+}
+
+/// Where and what to insert for the crate-level attribute: after the
+/// crate's inner attributes, separated from a leading doc comment by a
+/// blank line.
+fn deny_insert(text: &str, syntax: &syn::File) -> (usize, String) {
+    let attr = format!("#![deny({})]\n", LINT);
+    match syntax.attrs.last() {
+        None => (0, attr),
+        Some(last) => {
+            let line = items::end_line(last.span());
+            let at = offset(text, (line + 1, 0));
+            if last.path().is_ident("doc") {
+                (at, format!("\n{}", attr))
+            } else {
+                (at, attr)
+            }
+        }
+    }
+}
+
+/// Byte offset of `pos` in `text`; past the end clamps to the end.
+fn offset(text: &str, (line, column): Pos) -> usize {
+    let mut at = 0;
+    for (i, l) in text.split_inclusive('\n').enumerate() {
+        if i + 1 == line {
+            return at
+                + l.char_indices()
+                    .nth(column)
+                    .map_or(l.trim_end_matches('\n').len(), |(b, _)| b);
+        }
+        at += l.len();
+    }
+    text.len()
+}
+
+/// Walks an `unsafe fn` body and decides which spans to wrap.
+struct Wrapper<'a> {
+    ops: &'a [(Pos, Pos)],
+    spans: Vec<(Pos, Pos)>,
+}
+
+impl Wrapper<'_> {
+    fn contains_op(&self, (start, end): (Pos, Pos)) -> bool {
+        self.ops.iter().any(|&(s, e)| s >= start && e <= end)
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Local(local) => {
+                    if let Some(init) = &local.init {
+                        self.expr(&init.expr);
+                        if let Some((_, diverge)) = &init.diverge {
+                            self.expr(diverge);
+                        }
+                    }
+                }
+                Stmt::Expr(expr, _) => self.expr(expr),
+                Stmt::Macro(mac) => self.wrap(span_of(&mac.mac)),
+                Stmt::Item(_) => {}
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        if !self.contains_op(span_of(expr)) {
+            return;
+        }
+        match expr {
+            Expr::Unsafe(_) => {}
+            Expr::Block(e) => self.block(&e.block),
+            Expr::If(e) => {
+                self.expr(&e.cond);
+                self.block(&e.then_branch);
+                if let Some((_, otherwise)) = &e.else_branch {
+                    self.expr(otherwise);
+                }
+            }
+            Expr::Let(e) => self.expr(&e.expr),
+            Expr::While(e) => {
+                self.expr(&e.cond);
+                self.block(&e.body);
+            }
+            Expr::ForLoop(e) => {
+                self.expr(&e.expr);
+                self.block(&e.body);
+            }
+            Expr::Loop(e) => self.block(&e.body),
+            Expr::Match(e) => {
+                self.expr(&e.expr);
+                for arm in &e.arms {
+                    if let Some((_, guard)) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                }
+            }
+            Expr::Return(e) => {
+                if let Some(value) = &e.expr {
+                    self.expr(value);
+                }
+            }
+            Expr::Closure(e) => self.expr(&e.body),
+            _ => self.wrap(span_of(expr)),
+        }
+    }
+
+    fn wrap(&mut self, span: (Pos, Pos)) {
+        if self.contains_op(span) {
+            self.spans.push(span);
+        }
+    }
+}
+
+/// Start and end of a node, not counting its outer attributes: a block
+/// can't start with `#[cfg(..)]` on an expression.
+fn span_of(node: &impl ToTokens) -> (Pos, Pos) {
+    let tokens: Vec<TokenTree> = node.to_token_stream().into_iter().collect();
+    let mut first = 0;
+    while let [TokenTree::Punct(p), TokenTree::Group(_), ..] = &tokens[first..] {
+        if p.as_char() != '#' {
+            break;
+        }
+        first += 2;
+    }
+    let start = tokens
+        .get(first)
+        .map_or_else(Span::call_site, TokenTree::span);
+    let end = tokens.last().map_or_else(Span::call_site, TokenTree::span);
+    (pos(start.start()), pos(end.end()))
+}
+
+fn pos(at: proc_macro2::LineColumn) -> Pos {
+    (at.line, at.column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        let stdout = concat!(
+            r#"{"reason":"compiler-artifact"}"#,
+            "\n",
+            r#"{"reason":"compiler-message","message":{"message":"call to unsafe function `raw_alloc` is unsafe and requires unsafe block","code":{"code":"E0133"},"level":"warning","spans":[{"file_name":"memory_lib/src/lib.rs","line_start":7,"column_start":5,"line_end":7,"column_end":17,"is_primary":true}]}}"#,
+            "\n",
+            r#"{"reason":"compiler-message","message":{"message":"call to unsafe function `raw_alloc` is unsafe and requires unsafe block","code":{"code":"E0133"},"level":"error","spans":[{"file_name":"memory_lib/src/lib.rs","line_start":9,"column_start":5,"line_end":9,"column_end":17,"is_primary":true}]}}"#,
+        );
+        let ops = parse_messages(stdout);
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].file, "memory_lib/src/lib.rs");
+        assert_eq!((ops[0].start, ops[0].end), ((7, 4), (7, 16)));
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let source = "\
+//! Crate docs
+
+/// Docs
+unsafe fn raw(p: *mut i32, n: usize) -> i32 {
+    let layout = n * 4;
+    let v = *p + layout as i32;
+    for i in 0..n {
+        p.add(i).write(0)
+    }
+    match helper(p) {
+        0 => v,
+        _ => *p,
+    }
+}
+";
+        // The operations the compiler would report
+        let ops = [
+            ((6, 12), (6, 14)),
+            ((8, 8), (8, 16)),
+            ((8, 8), (8, 25)),
+            ((10, 10), (10, 19)),
+            ((12, 13), (12, 15)),
+        ];
+        let syntax = syn::parse_file(source).unwrap();
+        let (out, wrapped) = rewrite_file(
+            "src/lib.rs",
+            &["krate".to_string()],
+            source,
+            &syntax,
+            &ops,
+            true,
+        );
+        let expected = "\
+//! Crate docs
+
+#![deny(unsafe_op_in_unsafe_fn)]
+
+/// Docs
+unsafe fn raw(p: *mut i32, n: usize) -> i32 {
+    let layout = n * 4;
+    // SAFETY: TODO(MS-audit) explain why this is sound
+    let v = unsafe { *p + layout as i32 };
+    for i in 0..n {
+        // SAFETY: TODO(MS-audit) explain why this is sound
+        unsafe { p.add(i).write(0) }
+    }
+    // SAFETY: TODO(MS-audit) explain why this is sound
+    match unsafe { helper(p) } {
+        0 => v,
+        // SAFETY: TODO(MS-audit) explain why this is sound
+        _ => unsafe { *p },
+    }
+}
+";
+        assert_eq!(out, expected);
+        assert_eq!(wrapped.len(), 4);
+
+        // Already migrated: nothing left to do
+        let syntax = syn::parse_file(&out).unwrap();
+        let (again, wrapped) = rewrite_file(
+            "src/lib.rs",
+            &["krate".to_string()],
+            &out,
+            &syntax,
+            &[],
+            true,
+        );
+        assert_eq!(again, out);
+        assert!(wrapped.is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+//! The declarative unsafe policy.
+//!
+//! A workspace states which crates may contain unsafe code, and where, in
+//! a `safety-policy.toml` next to its `Cargo.toml`:
+//!
+//! ```toml
+//! [crates.memory_lib]
+//! unsafe_code = "allow"
+//! unsafe_fn = ["memory_lib", "memory_lib::convert"]
+//! safe = ["memory_lib::stats"]
+//!
+//! [crates.memory_audit]
+//! unsafe_code = "forbid"
+//! ```
+//!
+//! - `unsafe_code` is the level of the `unsafe_code` lint every crate root
+//!   of the package must declare. `allow` is rustc's default, so it may
+//!   also be left out. Under `forbid` and `deny` the package may contain
+//!   no unsafe code at all; a local `#[allow(unsafe_code)]` is reported.
+//! - `unsafe_fn` lists the modules that may define `unsafe fn`; everywhere
+//!   else the package only consumes unsafe APIs. Methods of trait impls
+//!   are exempt, since the trait decides whether they are unsafe.
+//! - `safe` lists modules that may contain no unsafe code at all.
+//!
+//! Module paths match exactly: a submodule is listed on its own. Only each
+//! package's `src/` is covered, and every workspace member needs an entry.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use syn::spanned::Spanned;
+use syn::Attribute;
+
+use crate::blocks;
+use crate::finding::{Finding, Rule};
+use crate::items::{self, ItemKind, Unit};
+use crate::workspace::{self, SourceFile};
+
+pub const FILE_NAME: &str = "safety-policy.toml";
+
+/// A level of the `unsafe_code` lint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Forbid,
+    Deny,
+    Warn,
+    Allow,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Forbid => "forbid",
+            Level::Deny => "deny",
+            Level::Warn => "warn",
+            Level::Allow => "allow",
+        }
+    }
+
+    /// Whether the package may contain unsafe code at all.
+    pub fn permits_unsafe(self) -> bool {
+        matches!(self, Level::Warn | Level::Allow)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CratePolicy {
+    pub unsafe_code: Level,
+    /// Modules that may define `unsafe fn`
+    #[serde(default)]
+    pub unsafe_fn: Vec<String>,
+    /// Modules that may contain no unsafe code
+    #[serde(default)]
+    pub safe: Vec<String>,
+}
+
+/// The parsed `safety-policy.toml`, keyed by package name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub crates: BTreeMap<String, CratePolicy>,
+}
+
+impl Policy {
+    pub fn parse(text: &str) -> Result<Policy, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+/// The `unsafe_code` level `attrs` declare, and the line declaring it. A
+/// `forbid` can't be lowered again, otherwise the last declaration wins.
+pub fn declared_level(attrs: &[Attribute]) -> Option<(Level, usize)> {
+    let mut declared = None;
+    for attr in attrs {
+        let level = match items::path_name(attr.path()).as_str() {
+            "forbid" => Level::Forbid,
+            "deny" => Level::Deny,
+            "warn" => Level::Warn,
+            "allow" => Level::Allow,
+            _ => continue,
+        };
+        if !items::tokens(&attr.meta).contains("unsafe_code") {
+            continue;
+        }
+        if declared.is_some_and(|(level, _)| level == Level::Forbid) {
+            continue;
+        }
+        declared = Some((level, items::line(attr.span())));
+    }
+    declared
+}
+
+/// Checks the workspace at `root` against its policy; findings are sorted
+/// by file and line.
+pub fn check(root: &Path) -> Result<Vec<Finding>, String> {
+    let path = root.join(FILE_NAME);
+    let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let policy = Policy::parse(&text).map_err(|e| format!("{}: {}", FILE_NAME, e))?;
+    let files = workspace::source_files(root)?;
+
+    let mut findings = Vec::new();
+    let mut entry = |line: usize, item: &str, message: String| {
+        findings.push(Finding {
+            rule: Rule::PolicyEntry,
+            file: FILE_NAME.to_string(),
+            line,
+            item: item.to_string(),
+            message,
+            related: None,
+        });
+    };
+    let line_of = |needle: &str| {
+        text.lines()
+            .position(|l| l.contains(needle))
+            .map_or(1, |i| i + 1)
+    };
+
+    let packages = workspace::packages(root)?;
+    let mut checked = Vec::new();
+    for package in &packages {
+        let Some(crate_policy) = policy.crates.get(&package.name) else {
+            entry(
+                1,
+                &package.crate_name(),
+                format!("package `{}` has no entry in {}", package.name, FILE_NAME),
+            );
+            continue;
+        };
+        let src = package.dir.join("src");
+        let package_files: Vec<&SourceFile> = files
+            .iter()
+            .filter(|f| f.package == package.name && f.path.starts_with(&src))
+            .collect();
+
+        let mut modules = BTreeSet::new();
+        for file in package_files {
+            let syntax = file.parse()?;
+            modules.insert(file.module_path.join("::"));
+            items::walk(&syntax, &file.module_path, &mut |unit| {
+                modules.insert(unit.module.clone());
+            });
+            checked.extend(check_file(
+                crate_policy,
+                &file.rel_path,
+                &file.module_path,
+                file.is_crate_root(),
+                &syntax,
+            ));
+        }
+        for module in crate_policy.unsafe_fn.iter().chain(&crate_policy.safe) {
+            if !modules.contains(module) {
+                entry(
+                    line_of(&format!("\"{}\"", module)),
+                    module,
+                    format!("no module `{}` in package `{}`", module, package.name),
+                );
+            }
+        }
+    }
+    for name in policy.crates.keys() {
+        if !packages.iter().any(|p| &p.name == name) {
+            entry(
+                line_of(&format!("[crates.{}]", name)),
+                name,
+                format!("no package `{}` in the workspace", name),
+            );
+        }
+    }
+
+    findings.extend(checked);
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(findings)
+}
+
+/// Checks one parsed file of a package with policy `policy`.
+/// `rel_path` is only used in the findings.
+pub fn check_file(
+    policy: &CratePolicy,
+    rel_path: &str,
+    module_path: &[String],
+    is_crate_root: bool,
+    syntax: &syn::File,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut report = |rule, line, item: &str, message: String| {
+        findings.push(Finding {
+            rule,
+            file: rel_path.to_string(),
+            line,
+            item: item.to_string(),
+            message,
+            related: None,
+        });
+    };
+
+    if is_crate_root {
+        let required = policy.unsafe_code;
+        let message = match declared_level(&syntax.attrs) {
+            Some((level, _)) if level == required => None,
+            None if required == Level::Allow => None,
+            Some((level, line)) => Some((
+                line,
+                format!(
+                    "crate root declares `#![{}(unsafe_code)]`, the policy requires `#![{}(unsafe_code)]`",
+                    level.as_str(),
+                    required.as_str()
+                ),
+            )),
+            None => Some((
+                1,
+                format!(
+                    "crate root declares no `unsafe_code` level, the policy requires `#![{}(unsafe_code)]`",
+                    required.as_str()
+                ),
+            )),
+        };
+        if let Some((line, message)) = message {
+            report(Rule::PolicyAttribute, line, &module_path[0], message);
+        }
+    }
+
+    items::walk(syntax, module_path, &mut |unit| {
+        let why_safe = if !policy.unsafe_code.permits_unsafe() {
+            Some(format!(
+                "the policy {}s unsafe code in this crate",
+                policy.unsafe_code.as_str()
+            ))
+        } else if policy.safe.contains(&unit.module) {
+            Some(format!("the policy marks `{}` safe", unit.module))
+        } else {
+            None
+        };
+
+        if let Some(why) = &why_safe {
+            for (line, what) in unsafe_code(&unit) {
+                report(
+                    Rule::PolicyUnsafeCode,
+                    line,
+                    &unit.path,
+                    format!("{} in `{}`, but {}", what, unit.module, why),
+                );
+            }
+        } else if unit.kind.is_fn()
+            && unit.is_unsafe
+            && unit.impl_trait.is_none()
+            && !policy.unsafe_fn.contains(&unit.module)
+        {
+            report(
+                Rule::PolicyUnsafeFn,
+                unit.line,
+                &unit.path,
+                format!(
+                    "unsafe fn `{}` defined in `{}`, which the policy does not list in `unsafe_fn`",
+                    unit.name, unit.module
+                ),
+            );
+        }
+    });
+    findings
+}
+
+/// The unsafe code `unit` declares or contains, by line.
+fn unsafe_code(unit: &Unit<'_>) -> Vec<(usize, &'static str)> {
+    let mut found: Vec<_> = blocks::unsafe_blocks(unit)
+        .iter()
+        .map(|block| (block.line, "unsafe block"))
+        .collect();
+    let own = match unit.kind {
+        ItemKind::Function | ItemKind::Method | ItemKind::TraitMethod => "unsafe fn",
+        ItemKind::Impl => "unsafe impl",
+        ItemKind::Trait => "unsafe trait",
+        ItemKind::ExternBlock => "extern block",
+        ItemKind::Static | ItemKind::Const => "",
+    };
+    if unit.kind == ItemKind::ExternBlock || (unit.is_unsafe && !own.is_empty()) {
+        found.insert(0, (unit.line, own));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_source(policy: &str, source: &str) -> Vec<(Rule, usize)> {
+        let policy = Policy::parse(policy).unwrap();
+        let syntax = syn::parse_file(source).unwrap();
+        let module_path = ["lib".to_string()];
+        check_file(
+            &policy.crates["lib"],
+            "src/lib.rs",
+            &module_path,
+            true,
+            &syntax,
+        )
+        .into_iter()
+        .map(|f| (f.rule, f.line))
+        .collect()
+    }
+
+    const SOURCE: &str = "\
+#![deny(unsafe_code)]
+pub unsafe fn raw() {}
+pub mod stats {
+    pub fn read(p: *const u8) -> u8 {
+        unsafe { *p }
+    }
+}
+";
+
+    #[test]
+    fn test_policy_levels_and_modules() {
+        let denied = "\
+[crates.lib]
+unsafe_code = \"deny\"
+";
+        assert_eq!(
+            check_source(denied, SOURCE),
+            [(Rule::PolicyUnsafeCode, 2), (Rule::PolicyUnsafeCode, 5)]
+        );
+
+        let modules = "\
+[crates.lib]
+unsafe_code = \"allow\"
+safe = [\"lib::stats\"]
+";
+        assert_eq!(
+            check_source(modules, SOURCE),
+            [
+                (Rule::PolicyAttribute, 1),
+                (Rule::PolicyUnsafeFn, 2),
+                (Rule::PolicyUnsafeCode, 5)
+            ]
+        );
+
+        let fine = "\
+[crates.lib]
+unsafe_code = \"allow\"
+unsafe_fn = [\"lib\"]
+";
+        assert_eq!(check_source(fine, &SOURCE.replace("deny", "allow")), []);
+        assert!(Policy::parse("[crates.lib]\nunsafe_code = \"allow\"\nunsafe_fns = []\n").is_err());
+    }
+}
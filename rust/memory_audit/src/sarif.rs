@@ -0,0 +1,195 @@
+//! SARIF 2.1.0 output for code-scanning dashboards.
+//!
+//! Every rule becomes a reporting descriptor whose id is the rule's stable
+//! id, with its help text as the fix suggestion. Every finding becomes a
+//! result with a file/line region, the item it is in as a logical
+//! location, and a fingerprint of rule, item and message that survives
+//! unrelated edits moving the code. Findings whose fix is a missing
+//! comment carry that comment as a SARIF fix, an insertion ready to apply.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::comments::{DISCHARGE, RATIONALE};
+use crate::finding::{Finding, Rule};
+use crate::hash::hash;
+use crate::{baseline, check, leaks, ledger, policy, workspace};
+
+pub const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Base id result locations are relative to: the workspace root.
+pub const SRCROOT: &str = "%SRCROOT%";
+
+/// Runs every check on the workspace at `root`: the comment conventions,
+/// leaky suppressions, the safety ledgers, the unsafe-count baselines and,
+/// if the workspace has a `safety-policy.toml`, the policy.
+pub fn findings(root: &Path) -> Result<Vec<Finding>, String> {
+    let files = workspace::source_files(root)?;
+    let mut findings = check::check(&files)?;
+    findings.extend(leaks::leaks(&files)?);
+    findings.extend(ledger::check(root)?);
+    findings.extend(baseline::check(root)?.findings);
+    if root.join(policy::FILE_NAME).exists() {
+        findings.extend(policy::check(root)?);
+    }
+    findings.sort_by(|a, b| (&a.file, a.line, a.rule).cmp(&(&b.file, b.line, b.rule)));
+    Ok(findings)
+}
+
+/// The SARIF log for `findings`. Sources under `root` are read to indent
+/// suggested fixes like the code they go above.
+pub fn log(root: &Path, findings: &[Finding]) -> Value {
+    let rules: Vec<Value> = Rule::ALL
+        .iter()
+        .map(|&rule| {
+            json!({
+                "id": rule.id(),
+                "name": format!("{:?}", rule),
+                "shortDescription": { "text": rule.description() },
+                "help": { "text": rule.help() },
+                "defaultConfiguration": { "level": level(rule) },
+            })
+        })
+        .collect();
+
+    let mut sources: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let lines = sources.entry(finding.file.clone()).or_insert_with(|| {
+                fs::read_to_string(root.join(&finding.file))
+                    .map(|text| text.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            });
+            result(finding, lines)
+        })
+        .collect();
+
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "memory_audit",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }]
+    })
+}
+
+/// Leaky suppressions are judgement calls with known, accepted instances;
+/// everything else is a broken rule.
+fn level(rule: Rule) -> &'static str {
+    match rule {
+        Rule::LeakySuppression => "warning",
+        _ => "error",
+    }
+}
+
+fn physical(file: &str, line: usize) -> Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": file, "uriBaseId": SRCROOT },
+            "region": { "startLine": line },
+        }
+    })
+}
+
+fn result(finding: &Finding, lines: &[String]) -> Value {
+    let rule_index = Rule::ALL.iter().position(|&r| r == finding.rule);
+    let mut location = physical(&finding.file, finding.line);
+    location["logicalLocations"] = json!([{ "fullyQualifiedName": finding.item }]);
+
+    let mut result = json!({
+        "ruleId": finding.rule.id(),
+        "ruleIndex": rule_index,
+        "level": level(finding.rule),
+        "message": { "text": finding.message },
+        "locations": [location],
+        "partialFingerprints": {
+            "memoryAudit/v1": hash(&format!(
+                "{}\n{}\n{}",
+                finding.rule.id(),
+                finding.item,
+                finding.message
+            )),
+        },
+    });
+    if let Some(related) = &finding.related {
+        let mut location = physical(&related.file, related.line);
+        location["id"] = json!(0);
+        location["message"] = json!({ "text": related.item });
+        result["relatedLocations"] = json!([location]);
+    }
+    if let Some(fix) = fix(finding, lines) {
+        result["fixes"] = json!([fix]);
+    }
+    result
+}
+
+/// An insertion of the comment a finding is missing, if that is the fix.
+fn fix(finding: &Finding, lines: &[String]) -> Option<Value> {
+    let (description, mut line, mut text) = match finding.rule {
+        Rule::MissingDischarge => (
+            "Add a discharge comment",
+            finding.line,
+            vec![format!(
+                "// {} TODO(MS-audit) explain why this is sound",
+                DISCHARGE
+            )],
+        ),
+        Rule::MissingSafetyDoc => (
+            "Add a # Safety doc section",
+            finding.line,
+            vec![
+                "/// # Safety".to_string(),
+                "///".to_string(),
+                "/// TODO(MS-audit) state what callers must guarantee".to_string(),
+            ],
+        ),
+        Rule::MissingImplRationale => (
+            "Add a safety comment",
+            finding.line,
+            vec![format!(
+                "// {} TODO(MS-audit) explain why this is sound",
+                RATIONALE
+            )],
+        ),
+        _ => return None,
+    };
+    let code = lines.get(line.checked_sub(1)?)?;
+    let indent = &code[..code.len() - code.trim_start().len()];
+
+    // Comments go above the item's attributes, after its doc comment
+    if finding.rule != Rule::MissingDischarge {
+        while line > 1 && lines[line - 2].trim_start().starts_with("#[") {
+            line -= 1;
+        }
+    }
+    if finding.rule == Rule::MissingSafetyDoc
+        && line > 1
+        && lines[line - 2].trim_start().starts_with("///")
+    {
+        text.insert(0, "///".to_string());
+    }
+    let inserted: String = text.iter().map(|l| format!("{}{}\n", indent, l)).collect();
+
+    Some(json!({
+        "description": { "text": description },
+        "artifactChanges": [{
+            "artifactLocation": { "uri": finding.file, "uriBaseId": SRCROOT },
+            "replacements": [{
+                "deletedRegion": { "startLine": line, "startColumn": 1, "endColumn": 1 },
+                "insertedContent": { "text": inserted },
+            }],
+        }],
+    }))
+}
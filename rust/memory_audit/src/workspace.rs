@@ -0,0 +1,242 @@
+//! Finding and parsing the Rust sources of a workspace.
+//!
+//! Discovery only reads `Cargo.toml` files and walks directories, so it works
+//! offline and without building anything. Every `.rs` file below a package
+//! is included, not just the ones reachable through `mod` declarations, so
+//! integration tests, benches and examples are audited too.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A package listed in the workspace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Package {
+    /// Package name as written in `Cargo.toml`
+    pub name: String,
+    /// Directory containing the package's `Cargo.toml`
+    pub dir: PathBuf,
+}
+
+impl Package {
+    /// The name the package's library is imported under.
+    pub fn crate_name(&self) -> String {
+        self.name.replace('-', "_")
+    }
+}
+
+/// One source file, and where it sits in its crate's module tree.
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    /// Package the file belongs to
+    pub package: String,
+    /// Path relative to the workspace root, with `/` separators
+    pub rel_path: String,
+    /// Absolute (or root-relative) path on disk
+    pub path: PathBuf,
+    /// Module path of the file: the crate name first (package-qualified for
+    /// targets other than the library, see `module_path`), then `mod` names
+    pub module_path: Vec<String>,
+}
+
+impl SourceFile {
+    /// `memory_lib::secret`-style rendering of `module_path`.
+    pub fn module(&self) -> String {
+        self.module_path.join("::")
+    }
+
+    /// Whether the file is the root of a crate (`lib.rs`, `main.rs`, a
+    /// test or bin target), where crate-level attributes go.
+    pub fn is_crate_root(&self) -> bool {
+        self.module_path.len() == 1 && !self.rel_path.ends_with("/mod.rs")
+    }
+
+    pub fn read(&self) -> io::Result<String> {
+        fs::read_to_string(&self.path)
+    }
+
+    /// Reads and parses the file into a syntax tree.
+    pub fn parse(&self) -> Result<syn::File, String> {
+        self.parse_with_text().map(|(_, syntax)| syntax)
+    }
+
+    /// Like `parse`, but also returns the source text, for the checks that
+    /// need comments.
+    pub fn parse_with_text(&self) -> Result<(String, syn::File), String> {
+        let text = self
+            .read()
+            .map_err(|e| format!("{}: {}", self.rel_path, e))?;
+        let syntax = syn::parse_file(&text).map_err(|e| {
+            let at = e.span().start();
+            format!("{}:{}:{}: {}", self.rel_path, at.line, at.column + 1, e)
+        })?;
+        Ok((text, syntax))
+    }
+}
+
+/// Lists the packages of the workspace (or single package) at `root`.
+pub fn packages(root: &Path) -> Result<Vec<Package>, String> {
+    let manifest = read_manifest(&root.join("Cargo.toml"))?;
+
+    let mut packages = Vec::new();
+    if let Some(name) = package_name(&manifest) {
+        packages.push(Package {
+            name,
+            dir: root.to_path_buf(),
+        });
+    }
+
+    let members = manifest
+        .get("workspace")
+        .and_then(|w| w.get("members"))
+        .and_then(|m| m.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for member in members.iter().filter_map(|m| m.as_str()) {
+        let dir = root.join(member);
+        let member_manifest = read_manifest(&dir.join("Cargo.toml"))?;
+        let name = package_name(&member_manifest)
+            .ok_or_else(|| format!("{}/Cargo.toml: no [package] name", member))?;
+        packages.push(Package { name, dir });
+    }
+
+    if packages.is_empty() {
+        return Err(format!(
+            "{}: neither a package nor a workspace",
+            root.display()
+        ));
+    }
+    Ok(packages)
+}
+
+/// Lists every `.rs` file of every package in the workspace at `root`,
+/// sorted by path.
+pub fn source_files(root: &Path) -> Result<Vec<SourceFile>, String> {
+    let mut files = Vec::new();
+    for package in packages(root)? {
+        let mut paths = Vec::new();
+        collect_rs_files(&package.dir, &package.dir, &mut paths)
+            .map_err(|e| format!("{}: {}", package.dir.display(), e))?;
+
+        for path in paths {
+            let in_package = path.strip_prefix(&package.dir).unwrap_or(&path);
+            let module_path = module_path(&package.crate_name(), in_package);
+            files.push(SourceFile {
+                package: package.name.clone(),
+                rel_path: slash_path(path.strip_prefix(root).unwrap_or(&path)),
+                path,
+                module_path,
+            });
+        }
+    }
+    files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(files)
+}
+
+fn read_manifest(path: &Path) -> Result<toml::Table, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    text.parse::<toml::Table>()
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn package_name(manifest: &toml::Table) -> Option<String> {
+    manifest
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Walks `dir`, skipping build output and nested packages.
+fn collect_rs_files(package_dir: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let nested_package = path != package_dir && path.join("Cargo.toml").exists();
+            if name == "target" || name.starts_with('.') || nested_package {
+                continue;
+            }
+            collect_rs_files(package_dir, &path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Maps a path inside a package to the module path of that file.
+///
+/// Files under `src/` belong to the package's crate (`src/lib.rs` is the
+/// root, `src/a/b.rs` is `crate::a::b`). Integration tests, benches,
+/// examples and `src/bin` files are crates of their own, named after the
+/// file as Cargo names those targets, and prefixed with the package and
+/// target directory (`memory_lib/tests::stats`) so that two packages' tests
+/// of the same name stay apart. The build script is
+/// `memory_lib/build_script_build`.
+pub fn module_path(crate_name: &str, in_package: &Path) -> Vec<String> {
+    let parts: Vec<String> = in_package
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    let stem = |file: &str| file.trim_end_matches(".rs").to_string();
+    let target = |dir: &str, file: &str| format!("{}/{}::{}", crate_name, dir, stem(file));
+
+    let (root, rest): (String, &[String]) = match parts.as_slice() {
+        [src, bin, rest @ ..] if src == "src" && bin == "bin" && !rest.is_empty() => {
+            (target("bin", &rest[0]), &rest[1..])
+        }
+        [src, rest @ ..] if src == "src" => (crate_name.to_string(), rest),
+        [dir, rest @ ..] if ["tests", "benches", "examples"].contains(&dir.as_str()) => {
+            match rest {
+                [] => (crate_name.to_string(), rest),
+                [first, more @ ..] => (target(dir, first), more),
+            }
+        }
+        [file] if file == "build.rs" => (format!("{}/build_script_build", crate_name), &[]),
+        _ => (crate_name.to_string(), parts.as_slice()),
+    };
+
+    let mut path = vec![root];
+    for (i, part) in rest.iter().enumerate() {
+        let last = i + 1 == rest.len();
+        if last && ["lib.rs", "main.rs", "mod.rs"].contains(&part.as_str()) {
+            break;
+        }
+        path.push(if last { stem(part) } else { part.clone() });
+    }
+    path
+}
+
+fn slash_path(path: &Path) -> String {
+    path.iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(path: &str) -> String {
+        module_path("memory_lib", Path::new(path)).join("::")
+    }
+
+    #[test]
+    fn test_module_paths() {
+        assert_eq!(module("src/lib.rs"), "memory_lib");
+        assert_eq!(module("src/main.rs"), "memory_lib");
+        assert_eq!(module("src/secret.rs"), "memory_lib::secret");
+        assert_eq!(module("src/raw/mod.rs"), "memory_lib::raw");
+        assert_eq!(module("src/raw/alloc.rs"), "memory_lib::raw::alloc");
+        assert_eq!(module("src/bin/tool.rs"), "memory_lib/bin::tool");
+        assert_eq!(module("tests/stats.rs"), "memory_lib/tests::stats");
+        assert_eq!(module("tests/common/mod.rs"), "memory_lib/tests::common");
+        assert_eq!(
+            module("benches/branded_index.rs"),
+            "memory_lib/benches::branded_index"
+        );
+        assert_eq!(module("build.rs"), "memory_lib/build_script_build");
+    }
+}
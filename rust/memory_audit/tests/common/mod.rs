@@ -0,0 +1,44 @@
+//! Helpers shared by the integration tests.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        let dest = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            if path.file_name().unwrap() != "target" {
+                copy_dir(&path, &dest)?;
+            }
+        } else {
+            fs::copy(&path, &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies `memory_lib` into this test's scratch directory `name` and
+/// returns the copy's directory.
+pub fn copy_memory_lib(name: &str) -> PathBuf {
+    let original = Path::new(env!("CARGO_MANIFEST_DIR")).join("../memory_lib");
+    let copy = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(name)
+        .join("memory_lib");
+    let _ = fs::remove_dir_all(&copy);
+    copy_dir(&original, &copy).unwrap();
+
+    // The copy lives under this workspace's target dir; make it a workspace
+    // of its own so cargo doesn't try to attach it to ours, and point its
+    // build dependency back at this crate
+    let manifest = copy.join("Cargo.toml");
+    let mut text = fs::read_to_string(&manifest).unwrap().replace(
+        "path = \"../memory_audit\"",
+        &format!("path = {:?}", env!("CARGO_MANIFEST_DIR")),
+    );
+    text.push_str("\n[workspace]\n");
+    fs::write(&manifest, text).unwrap();
+    copy
+}
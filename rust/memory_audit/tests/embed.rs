@@ -0,0 +1,58 @@
+//! Reads the safety summaries back out of the built `memory_app` binary.
+
+use std::process::Command;
+
+use memory_audit::{count, elf, embed, geiger, workspace};
+
+#[test]
+fn test_binary_carries_summaries_of_its_crates() {
+    let output = Command::new(env!("CARGO"))
+        .args(["build", "--offline", "--quiet", "-p", "memory_app"])
+        .args(["--features", "safety-summary"])
+        .args(["--message-format=json"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let executable = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find_map(|message| message["executable"].as_str().map(str::to_string))
+        .expect("cargo reported no executable");
+
+    let data = std::fs::read(executable).unwrap();
+    let summaries: Vec<embed::Summary> = elf::find_sections(&data, embed::SECTION)
+        .unwrap()
+        .into_iter()
+        .flat_map(embed::decode_all)
+        .collect();
+    let crates: Vec<&str> = summaries.iter().map(|s| s.crate_name.as_str()).collect();
+    assert!(crates.contains(&"memory_lib"), "{:?}", crates);
+    assert!(crates.contains(&"memory_app"), "{:?}", crates);
+
+    let lib = summaries
+        .iter()
+        .find(|s| s.crate_name == "memory_lib")
+        .unwrap();
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../memory_lib");
+    // memory_lib as memory_app builds it: default features plus embedding
+    let cfg = geiger::Cfg::host()
+        .unwrap()
+        .with_features(["std", "safety-summary"]);
+    let expected = embed::summarize(&dir.canonicalize().unwrap(), &cfg).unwrap();
+    // Only compiled code counts: not the unit tests' unsafe blocks
+    let all_of_src = count::count(
+        &workspace::source_files(&dir)
+            .unwrap()
+            .into_iter()
+            .filter(|f| f.rel_path.starts_with("src/"))
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+    assert!(expected.counts.blocks < all_of_src.total.blocks);
+    assert_eq!(lib, &expected);
+}
@@ -0,0 +1,5 @@
+//! A binary whose crate root disagrees with the policy.
+
+#![warn(unsafe_code)]
+
+fn main() {}
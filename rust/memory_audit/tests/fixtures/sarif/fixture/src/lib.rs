@@ -0,0 +1,43 @@
+//! Fixture for the SARIF test: one finding of each kind.
+
+pub mod raw {
+    /// Reads one byte.
+    ///
+    /// # Safety
+    ///
+    /// `p` must be valid for reads.
+    pub unsafe fn read(p: *const u8) -> u8 {
+        // SAFETY DISCHARGE: the caller guarantees `p` is valid for reads.
+        unsafe { *p }
+    }
+}
+
+pub mod safe {
+    pub fn first(bytes: &[u8]) -> u8 {
+        unsafe { *bytes.as_ptr() }
+    }
+}
+
+/// Writes one byte.
+#[inline]
+pub unsafe fn write(p: *mut u8, value: u8) {
+    // SAFETY DISCHARGE: the caller guarantees `p` is valid for writes.
+    unsafe { p.write(value) }
+}
+
+pub struct Handle(*mut u8);
+
+unsafe impl Send for Handle {}
+
+/// Hands out the buffer; only `free` takes it back.
+pub fn alloc() -> *mut u8 {
+    Box::into_raw(Box::new(0))
+}
+
+/// # Safety
+///
+/// `p` must come from `alloc`.
+pub unsafe fn free(p: *mut u8) {
+    // SAFETY DISCHARGE: `p` came from `Box::into_raw` in `alloc`.
+    drop(unsafe { Box::from_raw(p) });
+}
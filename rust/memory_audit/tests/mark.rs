@@ -0,0 +1,43 @@
+//! Marks a copy of `memory_lib` and checks that it still compiles.
+
+use std::fs;
+use std::process::Command;
+
+use memory_audit::{mark, workspace};
+
+mod common;
+
+#[test]
+fn test_marked_crate_still_compiles() {
+    let copy = common::copy_memory_lib("mark");
+    let manifest = copy.join("Cargo.toml");
+
+    let files = workspace::source_files(&copy).unwrap();
+    let first = mark::mark(&files, false).unwrap();
+    assert!(
+        first
+            .added
+            .iter()
+            .any(|m| m.item == "memory_lib::raw_alloc"),
+        "{:?}",
+        first.added
+    );
+    let lib = fs::read_to_string(copy.join("src/lib.rs")).unwrap();
+    assert!(lib.contains(mark::MARKER));
+
+    let second = mark::mark(&files, false).unwrap();
+    assert!(second.added.is_empty(), "marking is not idempotent");
+    assert_eq!(second.existing.len(), first.added.len());
+
+    let output = Command::new(env!("CARGO"))
+        .args(["check", "--offline", "--all-targets", "--manifest-path"])
+        .arg(&manifest)
+        .env("CARGO_TARGET_DIR", copy.join("target"))
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "marked crate failed to build:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,48 @@
+//! Migrates a copy of `memory_lib` and checks that it builds with the lint
+//! denied.
+
+use std::fs;
+use std::process::Command;
+
+use memory_audit::{migrate, workspace};
+
+mod common;
+
+#[test]
+fn test_migrated_crate_builds_with_lint_denied() {
+    let copy = common::copy_memory_lib("migrate");
+
+    let ops = migrate::unsafe_ops(&copy).unwrap();
+    assert!(
+        !ops.is_empty(),
+        "memory_lib has no unsafe operations to wrap"
+    );
+
+    let files = workspace::source_files(&copy).unwrap();
+    let migration = migrate::plan(&copy, &files, &ops).unwrap();
+    assert!(migration.roots.contains(&"src/lib.rs".to_string()));
+    migration.write().unwrap();
+
+    let lib = fs::read_to_string(copy.join("src/lib.rs")).unwrap();
+    assert!(lib.contains("#![deny(unsafe_op_in_unsafe_fn)]"));
+    assert!(lib.contains(migrate::PLACEHOLDER));
+
+    // Nothing left to migrate
+    assert!(migrate::unsafe_ops(&copy).unwrap().is_empty());
+    let files = workspace::source_files(&copy).unwrap();
+    let again = migrate::plan(&copy, &files, &[]).unwrap();
+    assert!(again.changes.is_empty(), "{}", again.diff());
+
+    let output = Command::new(env!("CARGO"))
+        .args(["check", "--offline", "--all-targets", "--all-features"])
+        .current_dir(&copy)
+        .env("RUSTFLAGS", "-Dwarnings")
+        .env("CARGO_TARGET_DIR", copy.join("target"))
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "migrated crate failed to build:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,149 @@
+//! Checks the SARIF log of a fixture workspace against the checked-in
+//! expected log and the SARIF 2.1.0 schema.
+//!
+//! The fixture has one finding of each kind. After an intended change to
+//! the output, regenerate the expected log with
+//! `memory_audit sarif tests/fixtures/sarif > tests/fixtures/sarif/expected.sarif`
+//! and add any new properties to the schema.
+
+use std::fs;
+use std::path::Path;
+
+use memory_audit::sarif;
+use serde_json::Value;
+
+fn read_json(path: &Path) -> Value {
+    let text = fs::read_to_string(path).unwrap();
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Validates `value` against `schema`, supporting the draft-07 keywords
+/// the checked-in schema uses. Errors name the JSON path that failed.
+fn validate(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.strip_prefix("#/definitions/").unwrap();
+        let definition = &root["definitions"][name];
+        assert!(!definition.is_null(), "unknown definition {}", reference);
+        return validate(root, definition, value, path, errors);
+    }
+    let mut fail = |message: String| errors.push(format!("{}: {}", path, message));
+
+    if let Some(expected) = schema["type"].as_str() {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            other => panic!("unsupported type {}", other),
+        };
+        if !matches {
+            return fail(format!("expected {}, found {}", expected, value));
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            fail(format!("{} is not one of {:?}", value, allowed));
+        }
+    }
+    if let (Some(minimum), Some(number)) = (schema["minimum"].as_i64(), value.as_i64()) {
+        if number < minimum {
+            fail(format!("{} is below the minimum {}", number, minimum));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let required = required.as_str().unwrap();
+            if !object.contains_key(required) {
+                fail(format!("missing required property {}", required));
+            }
+        }
+        for (key, property) in object {
+            let property_path = format!("{}.{}", path, key);
+            match (&schema["properties"][key], &schema["additionalProperties"]) {
+                (Value::Null, Value::Bool(false)) => {
+                    errors.push(format!("{}: property not in the schema", property_path))
+                }
+                (Value::Null, Value::Object(_)) => validate(
+                    root,
+                    &schema["additionalProperties"],
+                    property,
+                    &property_path,
+                    errors,
+                ),
+                (Value::Null, _) => {}
+                (declared, _) => validate(root, declared, property, &property_path, errors),
+            }
+        }
+    }
+
+    if let Some(array) = value.as_array() {
+        if let Some(min) = schema["minItems"].as_u64() {
+            if (array.len() as u64) < min {
+                errors.push(format!("{}: fewer than {} items", path, min));
+            }
+        }
+        if schema["uniqueItems"] == true {
+            for (i, item) in array.iter().enumerate() {
+                if array[..i].contains(item) {
+                    errors.push(format!("{}[{}]: duplicate item", path, i));
+                }
+            }
+        }
+        if !schema["items"].is_null() {
+            for (i, item) in array.iter().enumerate() {
+                validate(
+                    root,
+                    &schema["items"],
+                    item,
+                    &format!("{}[{}]", path, i),
+                    errors,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_sarif_matches_fixture_and_schema() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sarif");
+    let findings = sarif::findings(&fixtures).unwrap();
+    let log = sarif::log(&fixtures, &findings);
+
+    let schema = read_json(&fixtures.join("sarif-schema-2.1.0.json"));
+    let mut errors = Vec::new();
+    validate(&schema, &schema, &log, "$", &mut errors);
+    assert!(
+        errors.is_empty(),
+        "log does not match the schema:\n{}",
+        errors.join("\n")
+    );
+
+    // The expected log must itself be valid, or the comparison proves nothing
+    let expected = read_json(&fixtures.join("expected.sarif"));
+    validate(&schema, &schema, &expected, "$", &mut errors);
+    assert!(
+        errors.is_empty(),
+        "expected.sarif does not match the schema:\n{}",
+        errors.join("\n")
+    );
+    assert_eq!(log, expected);
+
+    // One result for every rule, so every rule's shape is covered
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    let rules = log["runs"][0]["tool"]["driver"]["rules"]
+        .as_array()
+        .unwrap();
+    for rule in rules {
+        assert!(
+            results.iter().any(|r| r["ruleId"] == rule["id"]),
+            "no result for rule {}",
+            rule["id"]
+        );
+    }
+    for result in results {
+        let index = result["ruleIndex"].as_u64().unwrap() as usize;
+        assert_eq!(rules[index]["id"], result["ruleId"]);
+    }
+}
@@ -0,0 +1,79 @@
+//! Builds manifests from the history of a scratch git repository.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use memory_audit::manifest::{self, TransitionKind};
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c",
+            "user.name=audit",
+            "-c",
+            "user.email=audit@example.com",
+        ])
+        .args(args)
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn commit(repo: &Path, lib: &str) {
+    fs::write(repo.join("krate/src/lib.rs"), lib).unwrap();
+    git(repo, &["add", "-A"]);
+    git(repo, &["commit", "-q", "-m", "change"]);
+}
+
+#[test]
+fn test_transitions_between_commits() {
+    let repo = Path::new(env!("CARGO_TARGET_TMPDIR")).join("transitions");
+    let _ = fs::remove_dir_all(&repo);
+    fs::create_dir_all(repo.join("krate/src")).unwrap();
+    fs::write(
+        repo.join("krate/Cargo.toml"),
+        "[package]\nname = \"krate\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    git(&repo, &["init", "-q"]);
+    commit(
+        &repo,
+        "pub fn read(p: &u8) -> u8 {\n    // SAFETY DISCHARGE: p is a reference\n    unsafe { *(p as *const u8) }\n}\n",
+    );
+    commit(
+        &repo,
+        "pub unsafe fn read(p: *const u8) -> u8 {\n    unsafe { *p }\n}\n",
+    );
+
+    // The audited root is a subdirectory of the repository
+    let root = repo.join("krate");
+    let (_, old) = manifest::at_revision(&root, "HEAD~1").unwrap();
+    let (commit, new) = manifest::at_revision(&root, "HEAD").unwrap();
+    // Full object id, whatever hash the repository uses
+    let head = Command::new("git")
+        .arg("-C")
+        .arg(&repo)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .expect("failed to run git");
+    assert!(commit.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(commit, String::from_utf8(head.stdout).unwrap().trim());
+    assert_eq!(old.items[0].file, "src/lib.rs");
+
+    let kinds: Vec<TransitionKind> = manifest::transitions(&old, &new)
+        .into_iter()
+        .map(|t| t.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            TransitionKind::SuppressorToPropagator,
+            TransitionKind::DischargeDeleted,
+            TransitionKind::BlockChanged,
+        ]
+    );
+    assert!(manifest::at_revision(&root, "no-such-rev").is_err());
+}
@@ -0,0 +1,67 @@
+//! Bounds-check savings of branded indices.
+//!
+//! Both loops read the same buffer through the same shuffled index list, so
+//! the optimizer cannot prove the indices in range. The checked loop pays
+//! `index < len` on every read; the branded loop paid it once, up front.
+//!
+//! Run with `cargo bench -p memory_lib --bench branded_index`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use memory_lib::SafeBuffer;
+
+const LEN: usize = 4096;
+const ROUNDS: u32 = 2000;
+
+fn shuffled_indices() -> Vec<usize> {
+    // Fixed LCG so runs are comparable
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut indices: Vec<usize> = (0..LEN).collect();
+    for i in (1..LEN).rev() {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        indices.swap(i, (state >> 33) as usize % (i + 1));
+    }
+    indices
+}
+
+fn time(mut f: impl FnMut() -> i32) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(f());
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let mut buffer = SafeBuffer::new(LEN);
+    for (i, slot) in buffer.as_mut_slice().iter_mut().enumerate() {
+        *slot = i as i32;
+    }
+    let indices = shuffled_indices();
+
+    let checked = time(|| {
+        let indices = black_box(&indices);
+        indices
+            .iter()
+            .map(|&i| buffer.get(i).unwrap())
+            .fold(0, i32::wrapping_add)
+    });
+
+    let branded = buffer.scope(|view| {
+        let tokens: Vec<_> = indices.iter().map(|&i| view.index(i).unwrap()).collect();
+        time(|| {
+            let tokens = black_box(&tokens);
+            tokens.iter().map(|&t| view.get(t)).fold(0, i32::wrapping_add)
+        })
+    });
+
+    println!("{LEN} reads through shuffled indices, mean of {ROUNDS} rounds:");
+    println!("  SafeBuffer::get (checked):  {:>10.2?}", checked);
+    println!("  BufferView::get (branded):  {:>10.2?}", branded);
+    println!(
+        "  speedup:                    {:>10.2}x",
+        checked.as_secs_f64() / branded.as_secs_f64()
+    );
+}
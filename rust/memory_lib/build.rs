@@ -0,0 +1,4 @@
+fn main() {
+    #[cfg(feature = "safety-summary")]
+    memory_audit::embed::build_script();
+}
@@ -0,0 +1,153 @@
+//! Rust Memory Safety Library - Branded Indices
+//!
+//! `SafeBuffer::get` checks `index < len` on every call. Inside
+//! `SafeBuffer::scope` the check is paid once, when an index is turned into
+//! an `Index<'id>` token; afterwards `view.get(idx)` reads without any check.
+//!
+//! The lifetime `'id` is a *brand*: every call to `scope` invents a fresh one
+//! that cannot be unified with any other, so a token from one buffer can never
+//! be used with another:
+//!
+//! ```compile_fail
+//! use memory_lib::SafeBuffer;
+//!
+//! let mut small = SafeBuffer::new(1);
+//! let mut large = SafeBuffer::new(100);
+//! large.scope(|large_view| {
+//!     let last = large_view.index(99).unwrap();
+//!     small.scope(|small_view| {
+//!         small_view.get(last); // ERROR: brands differ
+//!     });
+//! });
+//! ```
+//!
+//! Tokens cannot escape the scope either, because the brand cannot be named
+//! outside the closure:
+//!
+//! ```compile_fail
+//! use memory_lib::SafeBuffer;
+//!
+//! let mut buffer = SafeBuffer::new(10);
+//! let escaped = buffer.scope(|view| view.index(0).unwrap()); // ERROR
+//! ```
+
+use core::marker::PhantomData;
+
+use super::SafeBuffer;
+
+/// Invariant lifetime marker: `'id` can be neither shortened nor lengthened,
+/// so two distinct brands never coerce into each other.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+struct Brand<'id>(PhantomData<fn(&'id ()) -> &'id ()>);
+
+/// An index that has already been checked against the buffer branded `'id`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Index<'id> {
+    index: usize,
+    _brand: Brand<'id>,
+}
+
+impl Index<'_> {
+    /// The plain index this token stands for.
+    pub fn get(self) -> usize {
+        self.index
+    }
+}
+
+/// A view of a `SafeBuffer` for the duration of one `scope` call.
+pub struct BufferView<'id, 'a> {
+    buffer: &'a mut SafeBuffer,
+    _brand: Brand<'id>,
+}
+
+impl<'id> BufferView<'id, '_> {
+    pub fn len(&self) -> usize {
+        self.buffer.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.len == 0
+    }
+
+    /// Checks `index` against this buffer once and brands it.
+    pub fn index(&self, index: usize) -> Option<Index<'id>> {
+        if index >= self.buffer.len {
+            return None;
+        }
+        Some(Index {
+            index,
+            _brand: Brand(PhantomData),
+        })
+    }
+
+    /// Every valid index of this buffer, already branded.
+    pub fn indices(&self) -> impl Iterator<Item = Index<'id>> {
+        (0..self.buffer.len).map(|index| Index {
+            index,
+            _brand: Brand(PhantomData),
+        })
+    }
+
+    /// Read without a bounds check.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Pointer valid: `SafeBuffer` invariant, buffer borrowed for the scope
+    /// - Bounds: an `Index<'id>` only exists if `index < len` was checked
+    ///   against this buffer, and `len` cannot change while it is borrowed
+    pub fn get(&self, idx: Index<'id>) -> i32 {
+        // SAFETY DISCHARGE: idx.index < len by construction of Index<'id>
+        unsafe { self.buffer.ptr.add(idx.index).read() }
+    }
+
+    /// Write without a bounds check.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Same as `get`
+    /// - No aliasing: &mut self ensures exclusive access
+    pub fn set(&mut self, idx: Index<'id>, value: i32) {
+        // SAFETY DISCHARGE: idx.index < len by construction, exclusive via &mut self
+        unsafe { self.buffer.ptr.add(idx.index).write(value) };
+    }
+}
+
+impl SafeBuffer {
+    /// Runs `f` with a branded view of this buffer.
+    ///
+    /// # Safety Discharge
+    ///
+    /// The unchecked accesses in `BufferView` rely on three facts:
+    /// - Fresh brand: `f` must accept *every* `'id`, so the brand it gets
+    ///   matches no other scope, and tokens from other buffers are rejected
+    /// - No escape: `R` cannot mention `'id`, so tokens die with the closure
+    /// - Stable length: the buffer is mutably borrowed for the whole scope,
+    ///   so it cannot be dropped or resized while tokens exist
+    pub fn scope<R>(&mut self, f: impl for<'id> FnOnce(BufferView<'id, '_>) -> R) -> R {
+        f(BufferView {
+            buffer: self,
+            _brand: Brand(PhantomData),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branded_access() {
+        let mut buf = SafeBuffer::new(5);
+        buf.scope(|mut view| {
+            assert!(view.index(5).is_none());
+            let idx: Vec<_> = view.indices().collect();
+            for &i in &idx {
+                view.set(i, i.get() as i32 * 10);
+            }
+            let total: i32 = idx.iter().map(|&i| view.get(i)).sum();
+            assert_eq!(total, 100);
+        });
+        assert_eq!(buf.as_slice(), &[0, 10, 20, 30, 40]);
+    }
+}
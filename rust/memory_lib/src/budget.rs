@@ -0,0 +1,383 @@
+//! Rust Memory Safety Library - Allocation Budgets
+//!
+//! An `AllocationBudget` caps how many bytes `raw_alloc` may hand out while
+//! it is entered. Budgets are entered per thread with `scope`, and the same
+//! budget can be passed explicitly to other threads (it is `Sync`) and
+//! entered there too, so one request can share one cap across workers.
+//!
+//! Once a budget is exhausted, fallible constructors such as
+//! `SafeBuffer::try_new` return `AllocError::BudgetExceeded`, and panicking
+//! ones such as `SafeBuffer::new` panic with the same message.
+//!
+//! Budgets nest: an allocation is charged to every budget entered on the
+//! current thread, so an inner budget can never let a request exceed the
+//! outer one. The budgets charged are recorded against the allocation, and
+//! its free is credited back to exactly those, whichever thread frees it and
+//! whatever is entered there at the time. That makes a budget a cap on live
+//! bytes, not on total traffic, and lets a buffer outlive the scope it was
+//! allocated in.
+//!
+//! ```
+//! use memory_lib::budget::AllocationBudget;
+//! use memory_lib::{AllocError, SafeBuffer};
+//!
+//! let request = AllocationBudget::new(1024);
+//! let (result, report) = request.scope(|| {
+//!     let _small = SafeBuffer::new(16);
+//!     SafeBuffer::try_new(1000).map(|_| ())
+//! });
+//! assert!(matches!(result, Err(AllocError::BudgetExceeded { .. })));
+//! assert_eq!(report.peak, 64);
+//! assert_eq!(report.rejected, 1);
+//! ```
+
+use core::cell::Cell;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::AllocError;
+
+/// A cap on live bytes allocated through `raw_alloc`.
+///
+/// Allocations charged to a budget keep its counters alive, so they may be
+/// freed after the budget itself is dropped.
+#[derive(Debug)]
+pub struct AllocationBudget {
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug)]
+struct Counters {
+    limit: usize,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+/// Usage of a budget, reported when a `scope` exits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetReport {
+    /// The budget's limit in bytes
+    pub limit: usize,
+    /// Bytes still charged to the budget when the scope exited
+    pub used: usize,
+    /// Most bytes charged at any one time
+    pub peak: usize,
+    /// Allocations refused because the budget was exhausted
+    pub rejected: usize,
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} bytes in use (peak {}), {} allocation(s) rejected",
+            self.used, self.limit, self.peak, self.rejected
+        )
+    }
+}
+
+impl AllocationBudget {
+    pub fn new(limit_bytes: usize) -> Self {
+        AllocationBudget {
+            counters: Arc::new(Counters {
+                limit: limit_bytes,
+                used: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+                rejected: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.counters.limit
+    }
+
+    pub fn used(&self) -> usize {
+        self.counters.used.load(Ordering::Relaxed)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.limit().saturating_sub(self.used())
+    }
+
+    /// Current usage, as it would be reported on scope exit.
+    pub fn report(&self) -> BudgetReport {
+        BudgetReport {
+            limit: self.limit(),
+            used: self.used(),
+            peak: self.counters.peak.load(Ordering::Relaxed),
+            rejected: self.counters.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Runs `f` with this budget entered on the current thread, and reports
+    /// its usage on exit.
+    ///
+    /// # Safety Discharge
+    ///
+    /// The thread-local chain stores raw pointers to `frame` and `self`:
+    /// - `frame` lives on this stack frame for the whole call
+    /// - `self` is borrowed for the whole call
+    /// - `_restore` unlinks the frame before either goes away, including
+    ///   when `f` panics (it is dropped before `frame`)
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> (R, BudgetReport) {
+        let frame = Frame {
+            budget: self,
+            parent: CURRENT.with(Cell::get),
+        };
+        CURRENT.with(|current| current.set(&frame));
+        let _restore = Restore(frame.parent);
+
+        let result = f();
+        (result, self.report())
+    }
+}
+
+impl Counters {
+    fn try_reserve(&self, bytes: usize) -> Result<(), AllocError> {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let next = match used.checked_add(bytes) {
+                Some(next) if next <= self.limit => next,
+                _ => {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(AllocError::BudgetExceeded {
+                        requested: bytes,
+                        remaining: self.limit.saturating_sub(used),
+                    });
+                }
+            };
+            match self
+                .used
+                .compare_exchange_weak(used, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return Ok(()),
+                Err(actual) => used = actual,
+            }
+        }
+    }
+
+    fn release(&self, bytes: usize) {
+        let before = self.used.fetch_sub(bytes, Ordering::Relaxed);
+        debug_assert!(before >= bytes, "budget released more than charged");
+    }
+}
+
+/// One entered budget, linked to the budget entered before it.
+struct Frame {
+    budget: *const AllocationBudget,
+    parent: *const Frame,
+}
+
+std::thread_local! {
+    static CURRENT: Cell<*const Frame> = const { Cell::new(ptr::null()) };
+}
+
+struct Restore(*const Frame);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.0));
+    }
+}
+
+/// Calls `f` on every budget entered on this thread, innermost first.
+///
+/// # Safety Discharge
+///
+/// - Every non-null pointer in the chain was set by a `scope` call that is
+///   still running, so both the frame and its budget are alive
+fn for_each_entered(mut f: impl FnMut(&Arc<Counters>) -> bool) {
+    let mut frame = CURRENT.try_with(Cell::get).unwrap_or(ptr::null());
+    while !frame.is_null() {
+        // SAFETY DISCHARGE: frame and budget outlive their scope call
+        let (budget, parent) = unsafe { (&*(*frame).budget, (*frame).parent) };
+        if !f(&budget.counters) {
+            return;
+        }
+        frame = parent;
+    }
+}
+
+/// The budgets one allocation was charged to.
+#[must_use]
+pub(crate) struct Charge {
+    budgets: Vec<Arc<Counters>>,
+    bytes: usize,
+}
+
+impl Charge {
+    /// Keeps the charge until `raw_dealloc` (or a release to a `Vec`) frees
+    /// `ptr`.
+    pub(crate) fn record(self, ptr: *mut i32) {
+        if self.budgets.is_empty() {
+            return;
+        }
+        let mut charges = charges();
+        charges.insert(ptr as usize, self);
+        TRACKED.store(charges.len(), Ordering::Relaxed);
+    }
+
+    /// Credits the bytes back, for an allocation that did not happen.
+    pub(crate) fn cancel(self) {
+        for budget in &self.budgets {
+            budget.release(self.bytes);
+        }
+    }
+}
+
+/// Charges of live allocations, by address. Addresses are unique while the
+/// allocations are live, and every free goes through `release`.
+static CHARGES: Mutex<BTreeMap<usize, Charge>> = Mutex::new(BTreeMap::new());
+
+/// Number of entries in `CHARGES`, updated under its lock, so frees skip
+/// the lock when no budget is in use. A free always sees the store made when
+/// its own allocation was recorded, since handing the pointer over orders it.
+static TRACKED: AtomicUsize = AtomicUsize::new(0);
+
+fn charges() -> std::sync::MutexGuard<'static, BTreeMap<usize, Charge>> {
+    // A panic while holding the lock cannot leave the map half-updated
+    CHARGES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Called by `try_raw_alloc` before allocating `bytes`.
+///
+/// Charges every entered budget, or none of them.
+pub(crate) fn charge(bytes: usize) -> Result<Charge, AllocError> {
+    let mut budgets = Vec::new();
+    let mut failure = None;
+    for_each_entered(|budget| match budget.try_reserve(bytes) {
+        Ok(()) => {
+            budgets.push(Arc::clone(budget));
+            true
+        }
+        Err(err) => {
+            failure = Some(err);
+            false
+        }
+    });
+
+    let charge = Charge { budgets, bytes };
+    if let Some(err) = failure {
+        // Undo the budgets charged before the one that refused
+        charge.cancel();
+        return Err(err);
+    }
+    // Only now is the charge final, so only now can it set a peak
+    for budget in &charge.budgets {
+        budget
+            .peak
+            .fetch_max(budget.used.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+    Ok(charge)
+}
+
+/// Charges `bytes` without checking limits, for memory adopted from
+/// elsewhere that `raw_dealloc` will later free.
+pub(crate) fn force_charge(ptr: *mut i32, bytes: usize) {
+    let mut budgets = Vec::new();
+    for_each_entered(|budget| {
+        let used = budget.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        budget.peak.fetch_max(used, Ordering::Relaxed);
+        budgets.push(Arc::clone(budget));
+        true
+    });
+    Charge { budgets, bytes }.record(ptr);
+}
+
+/// Called by `raw_dealloc` after freeing `ptr`: credits its bytes back to
+/// the budgets its allocation was charged to.
+pub(crate) fn release(ptr: *mut i32) {
+    if TRACKED.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let charge = {
+        let mut charges = charges();
+        let charge = charges.remove(&(ptr as usize));
+        TRACKED.store(charges.len(), Ordering::Relaxed);
+        charge
+    };
+    if let Some(charge) = charge {
+        charge.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SafeBuffer;
+
+    #[test]
+    fn test_nested_budgets() {
+        let outer = AllocationBudget::new(100);
+        let inner = AllocationBudget::new(1000);
+
+        let (((), inner_report), outer_report) = outer.scope(|| {
+            let _a = SafeBuffer::new(10);
+            inner.scope(|| {
+                // Fits the inner budget, but not what is left of the outer one
+                let err = SafeBuffer::try_new(20).err();
+                assert!(matches!(err, Some(AllocError::BudgetExceeded { requested: 80, remaining: 60 })));
+
+                let _b = SafeBuffer::new(15);
+                assert_eq!(outer.used(), 100);
+                assert_eq!(outer.remaining(), 0);
+            })
+        });
+
+        assert_eq!(inner_report.peak, 60);
+        assert_eq!(inner_report.used, 0);
+        assert_eq!(inner_report.rejected, 0);
+        assert_eq!(outer_report.peak, 100);
+        assert_eq!(outer_report.rejected, 1);
+        assert_eq!(outer_report.used, 0);
+    }
+
+    #[test]
+    fn test_free_after_scope_credits_budget() {
+        let budget = AllocationBudget::new(64);
+        for _ in 0..100 {
+            let buffer = SafeBuffer::try_new_in(4, &budget).unwrap();
+            drop(buffer);
+        }
+        assert_eq!(budget.used(), 0);
+        assert_eq!(budget.report().peak, 16);
+    }
+
+    #[test]
+    fn test_free_credits_only_charged_budgets() {
+        let charged = AllocationBudget::new(64);
+        let unrelated = AllocationBudget::new(64);
+
+        let older = SafeBuffer::new(2);
+        let buffer = SafeBuffer::try_new_in(4, &charged).unwrap();
+        unrelated.scope(|| {
+            let _b = SafeBuffer::new(1);
+            drop(older);
+            assert_eq!(unrelated.used(), 4);
+        });
+
+        // Freed on another thread, with no budget entered there
+        std::thread::spawn(move || drop(buffer)).join().unwrap();
+        assert_eq!(charged.used(), 0);
+        assert_eq!(unrelated.used(), 0);
+    }
+
+    #[test]
+    fn test_buffer_outlives_budget() {
+        let budget = AllocationBudget::new(64);
+        let buffer = SafeBuffer::try_new_in(4, &budget).unwrap();
+        drop(budget);
+        drop(buffer);
+    }
+
+    #[test]
+    #[should_panic(expected = "Allocation budget exceeded")]
+    fn test_panicking_constructor() {
+        AllocationBudget::new(8).scope(|| SafeBuffer::new(3));
+    }
+}
@@ -0,0 +1,211 @@
+//! Rust Memory Safety Library - Buffer Traits
+//!
+//! Every container in this crate ends up exposing a slice, but under its own
+//! accessor names (`as_slice`, `get_slice`, `get_range`, `first_half`, ...).
+//! `Buffer` and `BufferMut` give them one vocabulary - `len`, `get`, `view`
+//! and `view_mut` - so algorithms can be written once for any of them, as
+//! well as for arrays, slices and `Vec`.
+//!
+//! All accessors are checked and return `None` instead of panicking. The
+//! only thing an implementor provides is the slice itself; the lifetime of
+//! that slice is what makes every provided method safe.
+//!
+//! Note: with a trait in scope, `array.get(i)` and `vec.get(i)` resolve to
+//! `Buffer::get` (returning `Option<T>`) rather than the slice method
+//! (returning `Option<&T>`). Generic code is unaffected, since methods of a
+//! bound are always available without importing the trait.
+
+use alloc::vec::Vec;
+use core::ops::{Bound, RangeBounds};
+
+use crate::fixed::FixedBuffer;
+use crate::secret::SecretBuffer;
+use crate::span_example::DataContainer;
+use crate::SafeBuffer;
+
+/// Read access to a contiguous run of `Copy` elements.
+pub trait Buffer {
+    type Item: Copy;
+
+    /// The whole buffer as a slice.
+    fn as_slice(&self) -> &[Self::Item];
+
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads one element, or `None` if `index` is out of bounds.
+    fn get(&self, index: usize) -> Option<Self::Item> {
+        self.as_slice().get(index).copied()
+    }
+
+    /// Borrows a sub-range, or `None` if the range is out of bounds.
+    fn view<R: RangeBounds<usize>>(&self, range: R) -> Option<&[Self::Item]> {
+        self.as_slice().get(bounds(&range))
+    }
+}
+
+/// Write access on top of `Buffer`.
+pub trait BufferMut: Buffer {
+    /// The whole buffer as a mutable slice.
+    fn as_mut_slice(&mut self) -> &mut [Self::Item];
+
+    /// Writes one element, or fails if `index` is out of bounds.
+    fn set(&mut self, index: usize, value: Self::Item) -> Result<(), &'static str> {
+        match self.as_mut_slice().get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err("Index out of bounds"),
+        }
+    }
+
+    /// Mutably borrows a sub-range, or `None` if the range is out of bounds.
+    fn view_mut<R: RangeBounds<usize>>(&mut self, range: R) -> Option<&mut [Self::Item]> {
+        self.as_mut_slice().get_mut(bounds(&range))
+    }
+}
+
+fn bounds<R: RangeBounds<usize>>(range: &R) -> (Bound<usize>, Bound<usize>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+// ============================================================================
+// Containers in this crate
+// ============================================================================
+
+impl Buffer for SafeBuffer {
+    type Item = i32;
+
+    fn as_slice(&self) -> &[i32] {
+        SafeBuffer::as_slice(self)
+    }
+}
+
+impl BufferMut for SafeBuffer {
+    fn as_mut_slice(&mut self) -> &mut [i32] {
+        SafeBuffer::as_mut_slice(self)
+    }
+}
+
+impl Buffer for SecretBuffer {
+    type Item = i32;
+
+    fn as_slice(&self) -> &[i32] {
+        SecretBuffer::as_slice(self)
+    }
+}
+
+impl BufferMut for SecretBuffer {
+    fn as_mut_slice(&mut self) -> &mut [i32] {
+        SecretBuffer::as_mut_slice(self)
+    }
+}
+
+impl<T: Copy, const N: usize> Buffer for FixedBuffer<T, N> {
+    type Item = T;
+
+    fn as_slice(&self) -> &[T] {
+        FixedBuffer::as_slice(self)
+    }
+}
+
+impl<T: Copy, const N: usize> BufferMut for FixedBuffer<T, N> {
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        FixedBuffer::as_mut_slice(self)
+    }
+}
+
+impl Buffer for DataContainer {
+    type Item = i32;
+
+    fn as_slice(&self) -> &[i32] {
+        DataContainer::as_slice(self)
+    }
+}
+
+impl BufferMut for DataContainer {
+    fn as_mut_slice(&mut self) -> &mut [i32] {
+        DataContainer::as_mut_slice(self)
+    }
+}
+
+// ============================================================================
+// Standard containers
+// ============================================================================
+
+impl<T: Copy> Buffer for [T] {
+    type Item = T;
+
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Copy> BufferMut for [T] {
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T: Copy, const N: usize> Buffer for [T; N] {
+    type Item = T;
+
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Copy, const N: usize> BufferMut for [T; N] {
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T: Copy> Buffer for Vec<T> {
+    type Item = T;
+
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Copy> BufferMut for Vec<T> {
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<B: BufferMut<Item = i32> + ?Sized>(buffer: &mut B) {
+        buffer.set(1, 7).unwrap();
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.get(1), Some(7));
+        assert_eq!(buffer.get(4), None);
+        assert!(buffer.set(4, 0).is_err());
+        assert_eq!(buffer.view(1..3).map(<[i32]>::len), Some(2));
+        assert_eq!(buffer.view(..=4), None);
+        assert_eq!(buffer.view(3..5), None);
+        buffer.view_mut(2..).unwrap().fill(9);
+        assert_eq!(buffer.view(..), Some(&[buffer.get(0).unwrap(), 7, 9, 9][..]));
+    }
+
+    #[test]
+    fn test_every_container_behaves_the_same() {
+        check(&mut SafeBuffer::new(4));
+        check(&mut SecretBuffer::new(4));
+        check(&mut FixedBuffer::<i32, 4>::new());
+        check(&mut DataContainer::new(vec![1, 2, 3, 4]));
+        check(&mut [1, 2, 3, 4]);
+        check(&mut vec![1, 2, 3, 4]);
+        check(&mut [0, 1, 2, 3, 4, 5][1..5]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Rust Memory Safety Library - Bulk Operations
+//!
+//! Mutators for `SafeBuffer` that take user closures. A closure can
+//! panic at any element, so each operation is written to leave the buffer in
+//! a state that is still valid when unwinding starts:
+//!
+//! - Every element stays initialized (values may be partly updated)
+//! - `ptr`/`len` always describe the live allocation, so Drop frees it once
+//!
+//! `i32` has no destructor, so no element can be dropped twice; the only
+//! thing at stake on a panic is the buffer's own invariants.
+
+use super::SafeBuffer;
+
+impl SafeBuffer {
+    /// Replaces every element with `f(element)`, front to back.
+    ///
+    /// If `f` panics, elements before the panicking one hold new values and
+    /// the rest hold old ones.
+    pub fn map_in_place(&mut self, mut f: impl FnMut(i32) -> i32) {
+        for slot in self.as_mut_slice() {
+            *slot = f(*slot);
+        }
+    }
+
+    /// Replaces every element with the next value from `f`, front to back.
+    ///
+    /// If `f` panics, the buffer is left partly filled.
+    pub fn fill_with(&mut self, mut f: impl FnMut() -> i32) {
+        for slot in self.as_mut_slice() {
+            *slot = f();
+        }
+    }
+
+    /// Sorts by the key `f` extracts, keeping equal elements in order.
+    ///
+    /// If `f` panics, the buffer holds some permutation of its elements.
+    pub fn sort_by_key<K: Ord>(&mut self, f: impl FnMut(&i32) -> K) {
+        self.as_mut_slice().sort_by_key(f);
+    }
+
+    /// Returns a buffer of the elements for which `f` returns true, in
+    /// order, or `None` if there are none.
+    ///
+    /// This is not `Vec::retain`: a `SafeBuffer` never changes length, so
+    /// the buffer is consumed. When some elements are dropped, a new buffer
+    /// of the kept length is allocated, the kept elements are copied into it
+    /// and the original is freed. When every element is kept, the original
+    /// allocation is returned unchanged and nothing is allocated.
+    ///
+    /// If `f` panics, `self` is dropped while unwinding and freed once.
+    pub fn filtered(mut self, mut f: impl FnMut(i32) -> bool) -> Option<SafeBuffer> {
+        // Compact kept elements to the front; the tail still holds
+        // initialized (if stale) values the whole time
+        let mut kept = 0;
+        let slice = self.as_mut_slice();
+        for i in 0..slice.len() {
+            let value = slice[i];
+            if f(value) {
+                slice[kept] = value;
+                kept += 1;
+            }
+        }
+
+        if kept == 0 {
+            return None;
+        }
+        if kept == self.len {
+            return Some(self);
+        }
+        let mut shrunk = SafeBuffer::new(kept);
+        shrunk
+            .as_mut_slice()
+            .copy_from_slice(&self.as_slice()[..kept]);
+        Some(shrunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_operations() {
+        let mut buf = SafeBuffer::from(vec![5, 3, 8, 1]);
+        buf.map_in_place(|x| x * 2);
+        assert_eq!(buf.as_slice(), &[10, 6, 16, 2]);
+
+        buf.sort_by_key(|&x| core::cmp::Reverse(x));
+        assert_eq!(buf.as_slice(), &[16, 10, 6, 2]);
+
+        let buf = buf.filtered(|x| x > 5).unwrap();
+        assert_eq!(buf.as_slice(), &[16, 10, 6]);
+        assert!(buf.filtered(|_| false).is_none());
+
+        let mut buf = SafeBuffer::new(3);
+        let mut next = 0;
+        buf.fill_with(|| {
+            next += 1;
+            next
+        });
+        assert_eq!(buf.as_slice(), &[1, 2, 3]);
+    }
+}
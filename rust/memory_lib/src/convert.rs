@@ -0,0 +1,219 @@
+//! Rust Memory Safety Library - Conversions
+//!
+//! Moves data between `SafeBuffer` and the standard collections. `SafeBuffer`
+//! allocates through the global allocator with `Layout::array::<i32>(len)`,
+//! which is exactly what `Vec<i32>` uses for a capacity of `len`. Whenever
+//! the layouts agree, ownership of the allocation is handed over as-is and no
+//! element is copied. When they don't (a `Vec` with spare capacity), the data
+//! is copied into a fresh allocation instead.
+//!
+//! The raw-parts bridge connects `SafeBuffer` with the `unsafe_alloc` family:
+//! an allocation can move from the propagation world (raw pointers, caller
+//! upholds `# Safety`) to the suppression world (`SafeBuffer` upholds its
+//! invariants) and back, one call site at a time.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::ManuallyDrop;
+
+use super::SafeBuffer;
+
+impl SafeBuffer {
+    /// Converts into a `Vec<i32>` without copying.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Allocator: `raw_alloc` uses the global allocator, as `Vec` does
+    /// - Layout: `Layout::array::<i32>(len)` is the layout of a `Vec` with
+    ///   capacity `len`
+    /// - Initialized: every element is initialized by struct invariant
+    /// - Ownership: `ManuallyDrop` keeps our Drop from freeing memory the
+    ///   `Vec` now owns
+    pub fn into_vec(self) -> Vec<i32> {
+        let this = ManuallyDrop::new(self);
+        // The Vec frees it from now on, outside raw_dealloc
+        crate::note_released(this.ptr, this.len * core::mem::size_of::<i32>());
+        // SAFETY DISCHARGE: same allocator and layout, ownership transferred once
+        unsafe { Vec::from_raw_parts(this.ptr, this.len, this.len) }
+    }
+
+    /// Converts into a `Box<[i32]>` without copying.
+    ///
+    /// The intermediate `Vec` has `len == capacity`, so
+    /// `Vec::into_boxed_slice` does not reallocate.
+    pub fn into_boxed_slice(self) -> Box<[i32]> {
+        self.into_vec().into_boxed_slice()
+    }
+
+    /// Gives up ownership of the allocation without freeing it.
+    ///
+    /// The returned pointer and length satisfy the `unsafe_alloc` contract:
+    /// they may be used with `unsafe_read`/`unsafe_write` for offsets below
+    /// `len`, and must eventually be released with `unsafe_free(ptr, len)` or
+    /// handed back to `SafeBuffer::from_raw_parts`.
+    pub fn into_raw_parts(self) -> (*mut i32, usize) {
+        let this = ManuallyDrop::new(self);
+        (this.ptr, this.len)
+    }
+
+    /// Takes ownership of an allocation made by `unsafe_alloc`.
+    ///
+    /// # Safety
+    /// - `ptr` must have been returned by `unsafe_alloc(len)`, or by
+    ///   `SafeBuffer::into_raw_parts` with the same `len`, or be the pointer
+    ///   and length of a slice returned by `SafeBuffer::leak` that is no
+    ///   longer borrowed
+    /// - `len` must be positive
+    /// - `ptr` must not have been passed to `unsafe_free`, and must not be
+    ///   used or freed by the caller afterwards; the buffer's Drop frees it
+    pub unsafe fn from_raw_parts(ptr: *mut i32, len: usize) -> Self {
+        debug_assert!(!ptr.is_null() && len > 0);
+        SafeBuffer { ptr, len }
+    }
+
+    /// Leaks the buffer, returning a slice that lives for the rest of the
+    /// program.
+    ///
+    /// The memory can still be reclaimed by passing the slice's pointer and
+    /// length to `SafeBuffer::from_raw_parts`.
+    pub fn leak(self) -> &'static mut [i32] {
+        let (ptr, len) = self.into_raw_parts();
+        // SAFETY DISCHARGE: ptr valid for len elements and never freed, so the
+        // slice may be 'static; into_raw_parts gave up the only other owner
+        unsafe { core::slice::from_raw_parts_mut(ptr, len) }
+    }
+}
+
+impl From<Vec<i32>> for SafeBuffer {
+    /// Takes over the vector's allocation if it has no spare capacity,
+    /// copies otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the vector is empty, like `SafeBuffer::new(0)`.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Layout: taken over only when `capacity == len`, so Drop frees it
+    ///   with the same `Layout::array::<i32>(len)` it was allocated with
+    /// - Ownership: `ManuallyDrop` keeps the `Vec` from freeing it as well
+    fn from(vec: Vec<i32>) -> Self {
+        assert!(!vec.is_empty(), "Buffer length must be positive");
+
+        if vec.capacity() != vec.len() {
+            let mut buffer = SafeBuffer::new(vec.len());
+            buffer.as_mut_slice().copy_from_slice(&vec);
+            return buffer;
+        }
+
+        let mut vec = ManuallyDrop::new(vec);
+        let ptr = vec.as_mut_ptr();
+        // Not allocated by raw_alloc, but raw_dealloc will free it
+        crate::note_adopted(ptr, vec.len() * core::mem::size_of::<i32>());
+        SafeBuffer {
+            ptr,
+            len: vec.len(),
+        }
+    }
+}
+
+impl From<Box<[i32]>> for SafeBuffer {
+    /// Takes over the boxed slice's allocation without copying.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is empty, like `SafeBuffer::new(0)`.
+    fn from(boxed: Box<[i32]>) -> Self {
+        // A boxed slice always converts to a Vec with capacity == len
+        SafeBuffer::from(Vec::from(boxed))
+    }
+}
+
+impl From<SafeBuffer> for Vec<i32> {
+    fn from(buffer: SafeBuffer) -> Self {
+        buffer.into_vec()
+    }
+}
+
+impl From<SafeBuffer> for Box<[i32]> {
+    fn from(buffer: SafeBuffer) -> Self {
+        buffer.into_boxed_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{unsafe_alloc, unsafe_free, unsafe_read, unsafe_write};
+
+    #[test]
+    fn test_round_trip_is_zero_copy() {
+        let mut buf = SafeBuffer::new(4);
+        buf.set(3, 7).unwrap();
+        let ptr = buf.as_slice().as_ptr();
+
+        let vec = buf.into_vec();
+        assert_eq!(vec.as_ptr(), ptr);
+        assert_eq!(vec, [0, 0, 0, 7]);
+
+        let buf = SafeBuffer::from(vec);
+        assert_eq!(buf.as_slice().as_ptr(), ptr);
+
+        let boxed = buf.into_boxed_slice();
+        assert_eq!(boxed.as_ptr(), ptr);
+
+        let buf = SafeBuffer::from(boxed);
+        assert_eq!(buf.as_slice().as_ptr(), ptr);
+        assert_eq!(buf.as_slice(), &[0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_spare_capacity_is_copied() {
+        let mut vec = Vec::with_capacity(8);
+        vec.extend_from_slice(&[1, 2, 3]);
+        let ptr = vec.as_ptr();
+
+        let buf = SafeBuffer::from(vec);
+        assert_ne!(buf.as_slice().as_ptr(), ptr);
+        assert_eq!(buf.as_slice(), &[1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Buffer length must be positive")]
+    fn test_empty_vec_rejected() {
+        let _ = SafeBuffer::from(Vec::new());
+    }
+
+    #[test]
+    fn test_raw_parts_bridge() {
+        // Propagation world -> suppression world
+        // SAFETY DISCHARGE: fresh allocation of 3, offset 0 in bounds,
+        // ownership handed to the SafeBuffer
+        let mut buf = unsafe {
+            let ptr = unsafe_alloc(3);
+            unsafe_write(ptr, 0, 5);
+            SafeBuffer::from_raw_parts(ptr, 3)
+        };
+        buf.set(2, 9).unwrap();
+        assert_eq!(buf.as_slice(), &[5, 0, 9]);
+
+        // ... and back again
+        let (ptr, len) = buf.into_raw_parts();
+        // SAFETY DISCHARGE: ptr/len from into_raw_parts, freed once
+        unsafe {
+            assert_eq!(unsafe_read(ptr, 2), 9);
+            unsafe_free(ptr, len);
+        }
+    }
+
+    #[test]
+    fn test_leak_can_be_reclaimed() {
+        let leaked: &'static mut [i32] = SafeBuffer::new(2).leak();
+        leaked[1] = 4;
+
+        let (ptr, len) = (leaked.as_mut_ptr(), leaked.len());
+        // SAFETY DISCHARGE: the leaked slice is not used again
+        let buf = unsafe { SafeBuffer::from_raw_parts(ptr, len) };
+        assert_eq!(buf.as_slice(), &[0, 4]);
+    }
+}
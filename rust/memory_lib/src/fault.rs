@@ -0,0 +1,189 @@
+//! Rust Memory Safety Library - Fault Injection
+//!
+//! Test support (the `fault-injection` feature): makes `raw_alloc` fail on
+//! demand, so out-of-memory paths can be exercised without actually running
+//! out of memory. An injected failure looks exactly like the allocator
+//! returning null: `try_new` constructors get `AllocError::OutOfMemory`, and
+//! panicking constructors panic with "Allocation failed".
+//!
+//! A `FaultPlan` describes which allocations fail; `inject` enables it for
+//! the current thread while a closure runs:
+//!
+//! ```
+//! use memory_lib::fault::{self, FaultPlan};
+//! use memory_lib::{AllocError, SafeBuffer};
+//!
+//! let (results, report) = fault::inject(FaultPlan::new().fail_nth(2), || {
+//!     (1..=3).map(|len| SafeBuffer::try_new(len).err()).collect::<Vec<_>>()
+//! });
+//! assert_eq!(results, [None, Some(AllocError::OutOfMemory), None]);
+//! assert_eq!(report.attempts, 3);
+//! assert_eq!(report.injected, 1);
+//! ```
+
+use std::cell::RefCell;
+
+/// Which allocations to fail. Rules combine: an allocation fails if any
+/// rule says so.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaultPlan {
+    nth: Option<u64>,
+    every: Option<u64>,
+    above: Option<usize>,
+    random: Option<(u64, u32)>,
+}
+
+impl FaultPlan {
+    /// A plan that fails nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the `n`th allocation (1-based) only.
+    pub fn fail_nth(mut self, n: u64) -> Self {
+        self.nth = Some(n);
+        self
+    }
+
+    /// Fail every `k`th allocation: the `k`th, `2k`th, and so on.
+    pub fn fail_every(mut self, k: u64) -> Self {
+        assert!(k > 0, "fail_every needs a positive period");
+        self.every = Some(k);
+        self
+    }
+
+    /// Fail every allocation larger than `bytes`.
+    pub fn fail_above(mut self, bytes: usize) -> Self {
+        self.above = Some(bytes);
+        self
+    }
+
+    /// Fail each allocation with probability `1 / one_in`, drawn from a PRNG
+    /// seeded with `seed`, so a failing run can be replayed exactly.
+    pub fn fail_randomly(mut self, seed: u64, one_in: u32) -> Self {
+        assert!(one_in > 0, "fail_randomly needs a positive ratio");
+        self.random = Some((seed, one_in));
+        self
+    }
+}
+
+/// What happened while a plan was in effect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaultReport {
+    /// Allocations attempted
+    pub attempts: u64,
+    /// Allocations failed on purpose
+    pub injected: u64,
+}
+
+struct Injector {
+    plan: FaultPlan,
+    rng: u64,
+    report: FaultReport,
+}
+
+impl Injector {
+    fn should_fail(&mut self, bytes: usize) -> bool {
+        self.report.attempts += 1;
+        let n = self.report.attempts;
+        let plan = self.plan;
+
+        let mut fail = plan.nth == Some(n)
+            || plan.every.is_some_and(|k| n.is_multiple_of(k))
+            || plan.above.is_some_and(|limit| bytes > limit);
+        if let Some((_, one_in)) = plan.random {
+            // Draw on every attempt, so the sequence doesn't depend on the
+            // other rules
+            fail |= self.next_random().is_multiple_of(u64::from(one_in));
+        }
+
+        if fail {
+            self.report.injected += 1;
+        }
+        fail
+    }
+
+    /// SplitMix64: tiny, seedable, and good enough for test schedules.
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+std::thread_local! {
+    static ACTIVE: RefCell<Option<Injector>> = const { RefCell::new(None) };
+}
+
+/// Called by `try_raw_alloc` before every allocation.
+pub(crate) fn should_fail(bytes: usize) -> bool {
+    ACTIVE
+        .try_with(|slot| {
+            slot.borrow_mut()
+                .as_mut()
+                .is_some_and(|injector| injector.should_fail(bytes))
+        })
+        .unwrap_or(false)
+}
+
+/// Runs `f` with `plan` in effect for allocations on the current thread.
+///
+/// The plan is removed again even if `f` panics, so tests can use
+/// `catch_unwind` around panicking constructors.
+pub fn inject<R>(plan: FaultPlan, f: impl FnOnce() -> R) -> (R, FaultReport) {
+    struct Restore(Option<Injector>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ACTIVE.with(|slot| *slot.borrow_mut() = self.0.take());
+        }
+    }
+
+    let injector = Injector {
+        plan,
+        rng: plan.random.map_or(0, |(seed, _)| seed),
+        report: FaultReport::default(),
+    };
+    let _restore = Restore(ACTIVE.with(|slot| slot.replace(Some(injector))));
+
+    let result = f();
+    let report = ACTIVE.with(|slot| slot.borrow().as_ref().map(|i| i.report));
+    (result, report.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(plan: FaultPlan, sizes: &[usize]) -> Vec<bool> {
+        inject(plan, || sizes.iter().map(|&bytes| should_fail(bytes)).collect()).0
+    }
+
+    #[test]
+    fn test_rules() {
+        let sizes = [4; 6];
+        assert_eq!(schedule(FaultPlan::new(), &sizes), [false; 6]);
+        assert_eq!(
+            schedule(FaultPlan::new().fail_every(3), &sizes),
+            [false, false, true, false, false, true]
+        );
+        assert_eq!(
+            schedule(FaultPlan::new().fail_nth(1).fail_above(10), &[4, 4, 16, 8]),
+            [true, false, true, false]
+        );
+        assert!(!should_fail(1 << 30), "plan must not outlive inject");
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let sizes = [4; 64];
+        let a = schedule(FaultPlan::new().fail_randomly(7, 4), &sizes);
+        let b = schedule(FaultPlan::new().fail_randomly(7, 4), &sizes);
+        let c = schedule(FaultPlan::new().fail_randomly(8, 4), &sizes);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.contains(&true) && a.contains(&false));
+    }
+}
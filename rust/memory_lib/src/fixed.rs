@@ -0,0 +1,167 @@
+//! Rust Memory Safety Library - Const-Generic Buffers
+//!
+//! `FixedBuffer<T, N>` has the same API as `SafeBuffer`, but its size is part
+//! of the type. When the index is a constant as well, the bounds check moves
+//! from runtime to compile time: `get_const::<I>()` and `split_const::<M>()`
+//! refuse to compile when `I` or `M` is out of range, so the unchecked access
+//! inside them never needs a runtime branch.
+//!
+//! Out-of-range constant indices are rejected at compile time:
+//!
+//! ```compile_fail,E0080
+//! use memory_lib::fixed::FixedBuffer;
+//!
+//! let buffer = FixedBuffer::<i32, 4>::new();
+//! let _ = buffer.get_const::<4>();
+//! ```
+//!
+//! ```compile_fail,E0080
+//! use memory_lib::fixed::FixedBuffer;
+//!
+//! let buffer = FixedBuffer::<i32, 4>::new();
+//! let _ = buffer.split_const::<5>();
+//! ```
+//!
+//! Zero-sized buffers are rejected just like `SafeBuffer::new(0)`:
+//!
+//! ```compile_fail,E0080
+//! use memory_lib::fixed::FixedBuffer;
+//!
+//! let _ = FixedBuffer::<i32, 0>::new();
+//! ```
+
+/// PUBLIC SAFE API - FixedBuffer
+///
+/// # Safety Invariants
+///
+/// - `data` always holds exactly `N` initialized elements
+/// - `N > 0`, checked at compile time by `new`
+pub struct FixedBuffer<T, const N: usize> {
+    data: [T; N],
+}
+
+impl<T: Copy + Default, const N: usize> FixedBuffer<T, N> {
+    /// Creates a new buffer filled with `T::default()`
+    pub fn new() -> Self {
+        const { assert!(N > 0, "Buffer length must be positive") };
+        FixedBuffer {
+            data: [T::default(); N],
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for FixedBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> FixedBuffer<T, N> {
+    pub fn len(&self) -> usize {
+        N
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Safe read with runtime bounds checking
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.data.get(index).copied()
+    }
+
+    /// Safe write with runtime bounds checking
+    pub fn set(&mut self, index: usize, value: T) -> Result<(), &'static str> {
+        match self.data.get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err("Index out of bounds"),
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// Returns a slice over a range with bounds checking.
+    pub fn get_slice(&self, start: usize, len: usize) -> Option<&[T]> {
+        self.data.get(start..start.checked_add(len)?)
+    }
+
+    /// Read at a constant index, with no runtime bounds check.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Bounds: `I < N` asserted at compile time, so the access is in range
+    ///   for every instantiation that compiles
+    pub fn get_const<const I: usize>(&self) -> T {
+        const { assert!(I < N, "Index out of bounds") };
+        // SAFETY DISCHARGE: I < N proven by the const assertion above
+        unsafe { *self.data.get_unchecked(I) }
+    }
+
+    /// Write at a constant index, with no runtime bounds check.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Bounds: `I < N` asserted at compile time
+    /// - No aliasing: &mut self ensures exclusive access
+    pub fn set_const<const I: usize>(&mut self, value: T) {
+        const { assert!(I < N, "Index out of bounds") };
+        // SAFETY DISCHARGE: I < N proven by the const assertion above
+        unsafe { *self.data.get_unchecked_mut(I) = value };
+    }
+
+    /// Splits the buffer into a fixed-size head of `M` elements and the rest.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Bounds: `M <= N` asserted at compile time
+    /// - Layout: the first `M` elements of `[T; N]` are laid out as `[T; M]`
+    /// - Lifetime: both halves borrow &self, cannot outlive the buffer
+    pub fn split_const<const M: usize>(&self) -> (&[T; M], &[T]) {
+        const { assert!(M <= N, "Split point out of bounds") };
+        // SAFETY DISCHARGE: M <= N proven by the const assertion above
+        let (head, tail) = unsafe { self.data.split_at_unchecked(M) };
+        // SAFETY DISCHARGE: head.len() == M, so it is exactly a [T; M]
+        let head = unsafe { &*(head.as_ptr() as *const [T; M]) };
+        (head, tail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_buffer_api() {
+        let mut buf = FixedBuffer::<i32, 10>::new();
+        buf.set(0, 42).unwrap();
+        assert_eq!(buf.get(0), Some(42));
+        assert_eq!(buf.get(10), None);
+        assert!(buf.set(10, 1).is_err());
+        assert_eq!(buf.get_slice(8, 3), None);
+    }
+
+    #[test]
+    fn test_const_access() {
+        let mut buf = FixedBuffer::<i32, 4>::new();
+        buf.set_const::<3>(7);
+        assert_eq!(buf.get_const::<3>(), 7);
+
+        let (head, tail) = buf.split_const::<1>();
+        assert_eq!(head, &[0]);
+        assert_eq!(tail, &[0, 0, 7]);
+
+        let (all, rest) = buf.split_const::<4>();
+        assert_eq!(all, &[0, 0, 0, 7]);
+        assert!(rest.is_empty());
+    }
+}
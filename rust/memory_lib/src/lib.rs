@@ -6,10 +6,99 @@
 //!
 //! Rust enforces both types equally - `unsafe fn` requires `unsafe` to call
 //! regardless of whether the caller is in the same module or a different crate.
+//!
+//! The `std` feature is on by default. Without it the crate builds on
+//! `core` + `alloc` only, and the printing demonstrations are left out.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::alloc::{alloc, dealloc, Layout};
+extern crate alloc;
 
+use alloc::alloc::{alloc, dealloc, Layout};
+use core::fmt;
+
+pub mod branded;
+#[cfg(feature = "std")]
+pub mod budget;
+pub mod buffer;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod fixed;
+pub mod secret;
 pub mod span_example;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod typestate;
+
+mod bulk;
+mod convert;
+
+// Unsafe counts, policy and ledger hash, kept in the `.msafety` section of
+// the rlib and of every binary linked with it (`memory_audit embedded`)
+#[cfg(feature = "safety-summary")]
+include!(concat!(env!("OUT_DIR"), "/safety_summary.rs"));
+
+/// Snapshot of allocation statistics (requires the `stats` feature).
+///
+/// See the `stats` module for per-test scoping.
+#[cfg(feature = "stats")]
+pub fn stats() -> stats::Stats {
+    stats::snapshot()
+}
+
+/// Why a fallible constructor could not allocate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocError {
+    /// A buffer of zero elements was requested
+    ZeroLength,
+    /// The byte size of the request overflows `isize`
+    InvalidLayout,
+    /// The global allocator returned null
+    OutOfMemory,
+    /// The current `AllocationBudget` (or one enclosing it) is exhausted
+    BudgetExceeded { requested: usize, remaining: usize },
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::ZeroLength => f.write_str("Buffer length must be positive"),
+            AllocError::InvalidLayout => f.write_str("Invalid layout"),
+            AllocError::OutOfMemory => f.write_str("Allocation failed"),
+            AllocError::BudgetExceeded {
+                requested,
+                remaining,
+            } => write!(
+                f,
+                "Allocation budget exceeded: requested {} bytes, {} bytes remaining",
+                requested, remaining
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AllocError {}
+
+/// Bookkeeping for memory at `ptr` that `raw_dealloc` will free, but that
+/// `raw_alloc` did not allocate (adopted from a `Vec`).
+fn note_adopted(ptr: *mut i32, bytes: usize) {
+    #[cfg(feature = "stats")]
+    stats::record_alloc(bytes);
+    #[cfg(feature = "std")]
+    budget::force_charge(ptr, bytes);
+    let _ = (ptr, bytes);
+}
+
+/// Bookkeeping for memory at `ptr` that `raw_alloc` allocated, but that
+/// something other than `raw_dealloc` will free (released to a `Vec`).
+fn note_released(ptr: *mut i32, bytes: usize) {
+    #[cfg(feature = "stats")]
+    stats::record_free(bytes);
+    #[cfg(feature = "std")]
+    budget::release(ptr);
+    let _ = (ptr, bytes);
+}
 
 // ============================================================================
 // CROSS-FUNCTION PROPAGATION (within this module)
@@ -19,19 +108,56 @@ pub mod span_example;
 // use `unsafe` blocks or mark the calling function as `unsafe fn`.
 
 /// Low-level allocation - marked unsafe, requires caller to use unsafe
+///
+/// Panics with the `AllocError` message if `try_raw_alloc` fails.
+///
+/// # Safety
+/// - The returned memory is uninitialized; write before reading
+/// - Caller must free it with `raw_dealloc` and the same `count`
 unsafe fn raw_alloc(count: usize) -> *mut i32 {
-    let layout = Layout::array::<i32>(count).expect("Invalid layout");
+    match try_raw_alloc(count) {
+        Ok(ptr) => ptr,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Low-level fallible allocation - consults the current `AllocationBudget`
+///
+/// # Safety
+/// - Same obligations as `raw_alloc` for the returned pointer
+unsafe fn try_raw_alloc(count: usize) -> Result<*mut i32, AllocError> {
+    let layout = Layout::array::<i32>(count).map_err(|_| AllocError::InvalidLayout)?;
+    #[cfg(feature = "fault-injection")]
+    if fault::should_fail(layout.size()) {
+        return Err(AllocError::OutOfMemory);
+    }
+    #[cfg(feature = "std")]
+    let charge = budget::charge(layout.size())?;
     let ptr = alloc(layout) as *mut i32;
     if ptr.is_null() {
-        panic!("Allocation failed");
+        #[cfg(feature = "std")]
+        charge.cancel();
+        return Err(AllocError::OutOfMemory);
     }
-    ptr
+    #[cfg(feature = "std")]
+    charge.record(ptr);
+    #[cfg(feature = "stats")]
+    stats::record_alloc(layout.size());
+    Ok(ptr)
 }
 
 /// Low-level deallocation - marked unsafe
+///
+/// # Safety
+/// - `ptr` must come from `raw_alloc(count)` (or be adopted with a matching
+///   `Layout::array::<i32>(count)`) and not be freed already
 unsafe fn raw_dealloc(ptr: *mut i32, count: usize) {
     let layout = Layout::array::<i32>(count).expect("Invalid layout");
     dealloc(ptr as *mut u8, layout);
+    #[cfg(feature = "stats")]
+    stats::record_free(layout.size());
+    #[cfg(feature = "std")]
+    budget::release(ptr);
 }
 
 /// Mid-level function that PROPAGATES unsafety (still unsafe fn)
@@ -41,6 +167,10 @@ unsafe fn raw_dealloc(ptr: *mut i32, count: usize) {
 ///
 /// CROSS-FUNCTION: Even within the same module, we must acknowledge the unsafe
 /// calls - either by being `unsafe fn` ourselves, or using `unsafe {}` blocks.
+///
+/// # Safety
+/// - Same obligations as `raw_alloc`
+#[cfg_attr(not(test), allow(dead_code))]
 unsafe fn mid_level_alloc_uninit(count: usize) -> *mut i32 {
     // Calling another unsafe fn in the same module still requires acknowledgment
     raw_alloc(count)
@@ -55,16 +185,36 @@ fn mid_level_alloc_zeroed(count: usize) -> *mut i32 {
     assert!(count > 0, "Count must be positive");
 
     // CROSS-FUNCTION propagation contained with unsafe block
+    // SAFETY DISCHARGE: count > 0 asserted above; every element is written
+    // below before the pointer is handed out
     let ptr = unsafe { raw_alloc(count) };
 
     // Initialize to zero - this makes it safe to read
     for i in 0..count {
+        // SAFETY DISCHARGE: i < count, within the allocation
         unsafe { ptr.add(i).write(0) };
     }
 
     ptr
 }
 
+/// Fallible counterpart of `mid_level_alloc_zeroed` (also SUPPRESSES)
+fn try_mid_level_alloc_zeroed(count: usize) -> Result<*mut i32, AllocError> {
+    if count == 0 {
+        return Err(AllocError::ZeroLength);
+    }
+
+    // SAFETY DISCHARGE: count > 0 checked above; zeroed below
+    let ptr = unsafe { try_raw_alloc(count)? };
+
+    for i in 0..count {
+        // SAFETY DISCHARGE: i < count, within the allocation
+        unsafe { ptr.add(i).write(0) };
+    }
+
+    Ok(ptr)
+}
+
 // ============================================================================
 // CROSS-MODULE PROPAGATION (exported to consumers)
 // ============================================================================
@@ -92,16 +242,30 @@ pub unsafe fn unsafe_alloc(count: usize) -> *mut i32 {
 }
 
 /// PUBLIC UNSAFE API - Frees memory
+///
+/// # Safety
+/// - `ptr` must have been returned by `unsafe_alloc` with the same `count`
+/// - `ptr` must not have been freed already
+/// - `ptr` must not be used after this call
 pub unsafe fn unsafe_free(ptr: *mut i32, count: usize) {
     raw_dealloc(ptr, count);
 }
 
 /// PUBLIC UNSAFE API - Read at offset
+///
+/// # Safety
+/// - `ptr` must point to a live allocation from `unsafe_alloc`
+/// - `offset` must be less than the `count` it was allocated with
 pub unsafe fn unsafe_read(ptr: *const i32, offset: usize) -> i32 {
     *ptr.add(offset)
 }
 
 /// PUBLIC UNSAFE API - Write at offset
+///
+/// # Safety
+/// - `ptr` must point to a live allocation from `unsafe_alloc`
+/// - `offset` must be less than the `count` it was allocated with
+/// - No other reference may be reading or writing the same element
 pub unsafe fn unsafe_write(ptr: *mut i32, offset: usize, value: i32) {
     *ptr.add(offset) = value;
 }
@@ -120,9 +284,10 @@ pub unsafe fn unsafe_write(ptr: *mut i32, offset: usize, value: i32) {
 /// # Safety Invariants
 ///
 /// This struct maintains the following invariants that make the public API safe:
-/// - `ptr` always points to valid memory of size `len * sizeof(i32)`
+/// - `ptr` always points to valid memory of size `len * sizeof(i32)`, allocated
+///   by the global allocator with `Layout::array::<i32>(len)`
 /// - `len` is immutable and accurately reflects the allocation size
-/// - Memory is zero-initialized at construction (safe to read)
+/// - Memory is initialized at construction (zeroed, or adopted from a `Vec`)
 /// - Memory is freed exactly once in Drop
 pub struct SafeBuffer {
     ptr: *mut i32,
@@ -146,6 +311,26 @@ impl SafeBuffer {
         SafeBuffer { ptr, len }
     }
 
+    /// Fallible `new`: reports zero lengths, allocation failure and
+    /// exhausted budgets as an `AllocError` instead of panicking.
+    ///
+    /// # Safety Discharge
+    ///
+    /// Same as `new`; count > 0 is checked by `try_mid_level_alloc_zeroed`.
+    pub fn try_new(len: usize) -> Result<Self, AllocError> {
+        // SAFETY DISCHARGE: count > 0 validated by callee, memory zero-initialized
+        let ptr = try_mid_level_alloc_zeroed(len)?;
+
+        Ok(SafeBuffer { ptr, len })
+    }
+
+    /// Fallible `new`, charged to `budget` on top of any budget already
+    /// entered on this thread.
+    #[cfg(feature = "std")]
+    pub fn try_new_in(len: usize, budget: &budget::AllocationBudget) -> Result<Self, AllocError> {
+        budget.scope(|| SafeBuffer::try_new(len)).0
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
@@ -198,7 +383,7 @@ impl SafeBuffer {
     /// - Aliasing: &self ensures no concurrent mutation
     pub fn as_slice(&self) -> &[i32] {
         // SAFETY DISCHARGE: ptr valid for len elements, lifetime tied to &self
-        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
     }
 
     /// Returns a mutable slice view of the entire buffer.
@@ -211,7 +396,7 @@ impl SafeBuffer {
     /// - Exclusive access: &mut self ensures no aliasing
     pub fn as_mut_slice(&mut self) -> &mut [i32] {
         // SAFETY DISCHARGE: ptr valid, exclusive access via &mut self
-        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) }
     }
 
     /// Returns a slice over a range with bounds checking.
@@ -225,20 +410,23 @@ impl SafeBuffer {
             return None;
         }
         // SAFETY DISCHARGE: bounds validated above
-        Some(unsafe { std::slice::from_raw_parts(self.ptr.add(start), len) })
+        Some(unsafe { core::slice::from_raw_parts(self.ptr.add(start), len) })
     }
 }
 
 impl Drop for SafeBuffer {
     fn drop(&mut self) {
         // Cross-function unsafe call, contained in Drop
+        // SAFETY DISCHARGE: ptr/len describe the live allocation, Drop runs once
         unsafe { unsafe_free(self.ptr, self.len) };
     }
 }
 
 // SAFETY: SafeBuffer can be sent to another thread because:
 // - `ptr` is exclusively owned (no aliasing possible due to Rust ownership)
-// - No thread-local state is used
+// - Freeing it does not depend on the freeing thread: the budgets it was
+//   charged to are looked up by address in a process-wide table, not in the
+//   thread-local chain of entered budgets
 // - The raw pointer is only accessed through &self or &mut self methods
 unsafe impl Send for SafeBuffer {}
 
@@ -263,17 +451,26 @@ pub mod propagation_chain {
     use super::*;
 
     /// Level 1: Directly calls raw unsafe function
+    ///
+    /// # Safety
+    /// - Caller must release the returned pointer with `cleanup`
     unsafe fn level1_unsafe() -> *mut i32 {
         raw_alloc(1)
     }
 
     /// Level 2: Calls level1, propagates unsafety
+    ///
+    /// # Safety
+    /// - Caller must release the returned pointer with `cleanup`
     unsafe fn level2_unsafe() -> *mut i32 {
         level1_unsafe()
     }
 
     /// Level 3: Calls level2, propagates unsafety
     /// This is PUBLIC - external code must use unsafe to call
+    ///
+    /// # Safety
+    /// - Caller must release the returned pointer with `cleanup`
     pub unsafe fn level3_propagate() -> *mut i32 {
         level2_unsafe()
     }
@@ -282,10 +479,16 @@ pub mod propagation_chain {
     /// This is PUBLIC and SAFE - external code needs no unsafe
     pub fn level3_suppress() -> *mut i32 {
         // The buck stops here - we take responsibility
+        // SAFETY DISCHARGE: level2_unsafe only allocates - it returns a
+        // fresh pointer from a successful raw_alloc(1) and reads nothing
         unsafe { level2_unsafe() }
     }
 
     /// Clean up helper
+    ///
+    /// # Safety
+    /// - `ptr` must come from `level3_propagate` or `level3_suppress`
+    /// - `ptr` must not have been cleaned up already
     pub unsafe fn cleanup(ptr: *mut i32) {
         raw_dealloc(ptr, 1);
     }
@@ -299,6 +502,7 @@ mod tests {
     fn test_cross_method_propagation() {
         // Within this module, we can call internal unsafe functions
         // but we STILL need unsafe blocks
+        // SAFETY DISCHARGE: all 5 elements written before reading, freed once
         unsafe {
             let ptr = mid_level_alloc_uninit(5);
             // Must initialize before reading
@@ -0,0 +1,209 @@
+//! Rust Memory Safety Library - Secret Buffers
+//!
+//! `SecretBuffer` is a `SafeBuffer` variant for key material. It suppresses
+//! the same unsafety as `SafeBuffer`, and additionally takes responsibility
+//! for what is left behind in memory once the buffer is gone:
+//!
+//! - Contents are wiped with volatile writes before the memory is freed
+//! - `Debug` output never shows the contents
+//! - Equality runs in constant time for buffers of equal length
+//! - No `Clone` impl, so secrets are never duplicated implicitly
+
+use core::fmt;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use super::{mid_level_alloc_zeroed, raw_dealloc, try_mid_level_alloc_zeroed, AllocError};
+
+/// PUBLIC SAFE API - SecretBuffer
+///
+/// # Safety Invariants
+///
+/// Same as `SafeBuffer`:
+/// - `ptr` always points to valid memory of size `len * sizeof(i32)`
+/// - `len` is immutable and accurately reflects the allocation size
+/// - Memory is zero-initialized at construction (safe to read)
+/// - Memory is wiped, then freed exactly once in Drop
+///
+/// `SecretBuffer` deliberately does not implement `Clone`:
+///
+/// ```compile_fail,E0599
+/// use memory_lib::secret::SecretBuffer;
+///
+/// let key = SecretBuffer::new(4);
+/// let copy = key.clone();
+/// ```
+pub struct SecretBuffer {
+    ptr: *mut i32,
+    len: usize,
+}
+
+impl SecretBuffer {
+    /// Creates a new zeroed secret buffer - NO unsafe required by caller
+    ///
+    /// # Safety Discharge
+    ///
+    /// - `mid_level_alloc_zeroed` requires count > 0: ensured by assert
+    /// - Memory must be wiped and freed: handled by Drop impl
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0, "Buffer length must be positive");
+
+        // SAFETY DISCHARGE: count > 0 validated above, memory zero-initialized
+        let ptr = mid_level_alloc_zeroed(len);
+
+        SecretBuffer { ptr, len }
+    }
+
+    /// Fallible `new`, see `SafeBuffer::try_new`.
+    pub fn try_new(len: usize) -> Result<Self, AllocError> {
+        // SAFETY DISCHARGE: count > 0 validated by callee, memory zero-initialized
+        let ptr = try_mid_level_alloc_zeroed(len)?;
+
+        Ok(SecretBuffer { ptr, len })
+    }
+
+    /// Creates a secret buffer holding a copy of `data`.
+    ///
+    /// The caller remains responsible for wiping `data` itself.
+    pub fn from_slice(data: &[i32]) -> Self {
+        let mut buffer = SecretBuffer::new(data.len());
+        buffer.as_mut_slice().copy_from_slice(data);
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Safe read with bounds checking
+    pub fn get(&self, index: usize) -> Option<i32> {
+        self.as_slice().get(index).copied()
+    }
+
+    /// Safe write with bounds checking
+    pub fn set(&mut self, index: usize, value: i32) -> Result<(), &'static str> {
+        match self.as_mut_slice().get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err("Index out of bounds"),
+        }
+    }
+
+    /// Returns a safe slice view of the secret.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Pointer valid: struct invariant
+    /// - Lifetime: returned slice borrows &self, cannot outlive buffer
+    pub fn as_slice(&self) -> &[i32] {
+        // SAFETY DISCHARGE: ptr valid for len elements, lifetime tied to &self
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Returns a mutable slice view of the secret.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Pointer valid: struct invariant
+    /// - Exclusive access: &mut self ensures no aliasing
+    pub fn as_mut_slice(&mut self) -> &mut [i32] {
+        // SAFETY DISCHARGE: ptr valid, exclusive access via &mut self
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Compares two secrets without branching on their contents.
+    ///
+    /// Every element is visited regardless of where the first difference
+    /// is, so timing reveals only the lengths, which are not secret.
+    pub fn ct_eq(&self, other: &SecretBuffer) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        let diff = self
+            .as_slice()
+            .iter()
+            .zip(other.as_slice())
+            .fold(0i32, |acc, (a, b)| acc | (a ^ b));
+        // Keep the optimizer from turning the fold into an early exit
+        core::hint::black_box(diff) == 0
+    }
+}
+
+impl PartialEq for SecretBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other)
+    }
+}
+
+impl Eq for SecretBuffer {}
+
+impl fmt::Debug for SecretBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretBuffer")
+            .field("len", &self.len)
+            .field("data", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl Drop for SecretBuffer {
+    /// Wipes the secret, then frees the memory.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Volatile writes: cannot be elided even though the memory is dead
+    /// - Compiler fence: the wipe cannot be reordered past the free
+    /// - Free: ptr/len come from construction, Drop runs exactly once
+    fn drop(&mut self) {
+        for i in 0..self.len {
+            // SAFETY DISCHARGE: i < len, ptr valid by invariant
+            unsafe { self.ptr.add(i).write_volatile(0) };
+        }
+        compiler_fence(Ordering::SeqCst);
+
+        // SAFETY DISCHARGE: allocated by mid_level_alloc_zeroed(len), freed once
+        unsafe { raw_dealloc(self.ptr, self.len) };
+    }
+}
+
+// SAFETY: SecretBuffer can be sent to another thread because:
+// - `ptr` is exclusively owned (no aliasing possible due to Rust ownership)
+// - Freeing it does not depend on the freeing thread: the budgets it was
+//   charged to are looked up by address in a process-wide, mutex-guarded
+//   table, not in the thread-local chain of entered budgets
+unsafe impl Send for SecretBuffer {}
+
+// SAFETY: SecretBuffer can be shared across threads because:
+// - All read access is through &self (shared reference)
+// - Mutation requires &mut self (exclusive reference)
+unsafe impl Sync for SecretBuffer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let key = SecretBuffer::from_slice(&[0x5EC2E7, 42]);
+        let shown = format!("{:?}", key);
+        assert!(shown.contains("REDACTED"));
+        assert!(!shown.contains("42"));
+    }
+
+    #[test]
+    fn test_ct_eq() {
+        let a = SecretBuffer::from_slice(&[1, 2, 3]);
+        let b = SecretBuffer::from_slice(&[1, 2, 3]);
+        let c = SecretBuffer::from_slice(&[1, 2, 4]);
+        let d = SecretBuffer::from_slice(&[1, 2]);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+    }
+}
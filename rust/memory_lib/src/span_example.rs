@@ -10,8 +10,14 @@
 //!
 //! Key difference from C#/Swift: Rust enforces lifetimes at COMPILE TIME,
 //! so use-after-free is impossible, not just detected at runtime.
+//!
+//! The `demonstrate_*` functions print, so they need the `std` feature.
+//! `DataContainer` and the slice helpers only need `alloc`.
+
+use alloc::vec::Vec;
 
 /// Demonstrates basic slice creation from arrays and vectors.
+#[cfg(feature = "std")]
 pub fn demonstrate_basic_slices() {
     println!("--- Slices from Arrays and Vectors ---");
 
@@ -38,6 +44,7 @@ pub fn demonstrate_basic_slices() {
 }
 
 /// Demonstrates mutable slices.
+#[cfg(feature = "std")]
 pub fn demonstrate_mutable_slices() {
     println!("--- Mutable Slices ---");
 
@@ -58,6 +65,7 @@ pub fn demonstrate_mutable_slices() {
 }
 
 /// Demonstrates zero-copy slicing operations.
+#[cfg(feature = "std")]
 pub fn demonstrate_slicing() {
     println!("--- Zero-Copy Slicing ---");
 
@@ -86,6 +94,7 @@ pub fn demonstrate_slicing() {
 }
 
 /// Demonstrates slices as function parameters.
+#[cfg(feature = "std")]
 pub fn demonstrate_function_parameters() {
     println!("--- Slices as Function Parameters ---");
 
@@ -102,14 +111,20 @@ pub fn demonstrate_function_parameters() {
     let doubled: Vec<i32> = map_slice(&data, |x| x * 2);
     println!("Doubled: {:?}", doubled);
 
+    // The same algorithms accept any Buffer, not just slices
+    let buffer = crate::SafeBuffer::from(vec![3, 1, 2]);
+    println!("Sum of SafeBuffer: {}", sum(&buffer));
+
     println!();
 }
 
 /// Demonstrates compile-time lifetime safety.
+#[cfg(feature = "std")]
 pub fn demonstrate_lifetime_safety() {
     println!("--- Compile-Time Lifetime Safety ---");
 
     // This is SAFE - slice lives as long as the data
+    #[allow(clippy::useless_vec)]
     let data = vec![1, 2, 3];
     let slice = &data[..];
     println!("Slice of vec: {:?}", slice);
@@ -129,6 +144,7 @@ pub fn demonstrate_lifetime_safety() {
 }
 
 /// Demonstrates safe iteration patterns.
+#[cfg(feature = "std")]
 pub fn demonstrate_iteration() {
     println!("--- Safe Iteration ---");
 
@@ -165,12 +181,14 @@ pub fn demonstrate_iteration() {
 }
 
 /// Demonstrates contrast with unsafe raw pointers.
+#[cfg(feature = "std")]
 pub fn demonstrate_contrast_with_pointers() {
     println!("--- Contrast: Slices vs Raw Pointers ---");
 
     let data = [10, 20, 30, 40, 50];
 
     // UNSAFE: Raw pointer approach - no bounds checking
+    // SAFETY DISCHARGE: offset 2 is within the 5-element array
     unsafe {
         let ptr = data.as_ptr();
         println!("Pointer access: {}", *ptr.add(2));
@@ -190,6 +208,7 @@ pub fn demonstrate_contrast_with_pointers() {
 }
 
 /// Demonstrates `get` for non-panicking access.
+#[cfg(feature = "std")]
 pub fn demonstrate_safe_access() {
     println!("--- Non-Panicking Access with get() ---");
 
@@ -221,6 +240,7 @@ pub fn demonstrate_safe_access() {
 /// Returning a slice allows callers to get a safe, bounds-checked view
 /// into internal state. Rust's lifetime system ensures the slice cannot
 /// outlive the data it references - enforced at COMPILE TIME.
+#[cfg(feature = "std")]
 pub fn demonstrate_returning_slices() {
     println!("--- Returning Slices (The Compelling Case) ---");
 
@@ -259,24 +279,34 @@ pub fn demonstrate_returning_slices() {
     println!();
 }
 
-// Helper functions that work with slices
+// Algorithms that work with any buffer: slices, arrays, Vec, and every
+// container in this crate. The traits are named by path rather than imported,
+// so `array.get(i)` in this module keeps its usual slice meaning.
 
-fn sum(slice: &[i32]) -> i32 {
-    slice.iter().sum()
+/// Sums the elements of any buffer.
+pub fn sum<B: crate::buffer::Buffer<Item = i32> + ?Sized>(buffer: &B) -> i32 {
+    buffer.as_slice().iter().sum()
 }
 
-fn sort_slice(slice: &mut [i32]) {
-    slice.sort();
+/// Sorts any mutable buffer in place.
+pub fn sort_slice<B: crate::buffer::BufferMut<Item = i32> + ?Sized>(buffer: &mut B) {
+    buffer.as_mut_slice().sort();
 }
 
-fn map_slice<F>(slice: &[i32], f: F) -> Vec<i32>
+/// Maps every element of any buffer into a new `Vec`.
+///
+/// To transform a `SafeBuffer` without allocating, use
+/// `SafeBuffer::map_in_place`.
+pub fn map_slice<B, F>(buffer: &B, f: F) -> Vec<i32>
 where
+    B: crate::buffer::Buffer<Item = i32> + ?Sized,
     F: Fn(i32) -> i32,
 {
-    slice.iter().map(|&x| f(x)).collect()
+    buffer.as_slice().iter().map(|&x| f(x)).collect()
 }
 
 /// Runs all slice demonstrations.
+#[cfg(feature = "std")]
 pub fn run_all_demonstrations() {
     println!("=== Rust Slice Examples ===");
     println!("Slices provide safe, bounds-checked access to contiguous memory.\n");
@@ -352,6 +382,15 @@ impl DataContainer {
         &self.data[mid..]
     }
 
+    /// Returns a mutable slice over the entire data.
+    ///
+    /// SAFETY DISCHARGE:
+    /// - &mut self ensures exclusive access (no aliasing)
+    /// - Lifetime tied to &mut self
+    pub fn as_mut_slice(&mut self) -> &mut [i32] {
+        &mut self.data
+    }
+
     /// Returns a mutable slice over the first half.
     ///
     /// SAFETY DISCHARGE:
@@ -390,6 +429,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn test_bounds_checking() {
         let data = [1, 2, 3];
         assert_eq!(data.get(0), Some(&1));
@@ -0,0 +1,205 @@
+//! Rust Memory Safety Library - Allocation Statistics
+//!
+//! With the `stats` feature, every `raw_alloc`/`raw_dealloc` updates a set of
+//! atomic counters, readable at any time with `memory_lib::stats()`. Without
+//! the feature, this module does not exist and the allocation paths contain
+//! no bookkeeping at all.
+//!
+//! Counters are process-wide, so they mix in allocations from every test
+//! running in parallel. `scope` gives a test its own view: inside the
+//! closure, `memory_lib::stats()` reports only what the current thread did
+//! since the scope began, and the final numbers are returned on exit.
+//!
+//! ```
+//! use memory_lib::{stats, SafeBuffer};
+//!
+//! let ((), used) = stats::scope(|| {
+//!     let buffer = SafeBuffer::new(16);
+//!     assert_eq!(memory_lib::stats().live_bytes, 64);
+//!     drop(buffer);
+//! });
+//! assert_eq!(used.allocations, 1);
+//! assert!(used.is_balanced());
+//! ```
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::cell::RefCell;
+
+/// Number of histogram buckets; see `Stats::histogram`.
+pub const BUCKETS: usize = 32;
+
+/// A snapshot of allocation activity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Bytes currently allocated and not yet freed
+    pub live_bytes: usize,
+    /// Highest `live_bytes` seen
+    pub peak_bytes: usize,
+    /// Number of allocations made
+    pub allocations: u64,
+    /// Number of allocations freed
+    pub frees: u64,
+    /// Allocation sizes, by power of two: bucket `k` counts allocations of
+    /// `2^(k-1) < bytes <= 2^k`; the last bucket also takes anything larger
+    pub histogram: [u64; BUCKETS],
+}
+
+impl Stats {
+    /// Allocations not yet freed.
+    ///
+    /// Zero when a scope freed more than it allocated, i.e. it dropped
+    /// allocations made before it began.
+    pub fn live_allocations(&self) -> u64 {
+        self.allocations.saturating_sub(self.frees)
+    }
+
+    /// True if everything allocated has been freed again.
+    pub fn is_balanced(&self) -> bool {
+        self.allocations == self.frees && self.live_bytes == 0
+    }
+
+    fn record_alloc(&mut self, bytes: usize) {
+        self.live_bytes += bytes;
+        self.peak_bytes = self.peak_bytes.max(self.live_bytes);
+        self.allocations += 1;
+        self.histogram[bucket(bytes)] += 1;
+    }
+
+    fn record_free(&mut self, bytes: usize) {
+        self.live_bytes = self.live_bytes.saturating_sub(bytes);
+        self.frees += 1;
+    }
+}
+
+/// The histogram bucket for an allocation of `bytes`.
+pub fn bucket(bytes: usize) -> usize {
+    bytes
+        .checked_next_power_of_two()
+        .map_or(BUCKETS - 1, |p| (p.trailing_zeros() as usize).min(BUCKETS - 1))
+}
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static FREES: AtomicU64 = AtomicU64::new(0);
+static HISTOGRAM: [AtomicU64; BUCKETS] = [const { AtomicU64::new(0) }; BUCKETS];
+
+std::thread_local! {
+    static SCOPE: RefCell<Option<Stats>> = const { RefCell::new(None) };
+}
+
+/// Called by `raw_alloc` (and adoptions of foreign allocations).
+pub(crate) fn record_alloc(bytes: usize) {
+    let live = LIVE_BYTES.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    HISTOGRAM[bucket(bytes)].fetch_add(1, Ordering::Relaxed);
+
+    with_scope(|scope| scope.record_alloc(bytes));
+}
+
+/// Called by `raw_dealloc` (and releases to foreign owners).
+pub(crate) fn record_free(bytes: usize) {
+    LIVE_BYTES.fetch_sub(bytes, Ordering::Relaxed);
+    FREES.fetch_add(1, Ordering::Relaxed);
+
+    with_scope(|scope| scope.record_free(bytes));
+}
+
+fn with_scope(f: impl FnOnce(&mut Stats)) {
+    // During thread teardown the slot may already be gone; nothing to record
+    let _ = SCOPE.try_with(|slot| {
+        if let Some(scope) = slot.borrow_mut().as_mut() {
+            f(scope);
+        }
+    });
+}
+
+/// Current statistics: the innermost `scope` on this thread if there is one,
+/// otherwise the process-wide totals.
+pub fn snapshot() -> Stats {
+    if let Some(scoped) = SCOPE.with(|slot| *slot.borrow()) {
+        return scoped;
+    }
+    Stats {
+        live_bytes: LIVE_BYTES.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        frees: FREES.load(Ordering::Relaxed),
+        histogram: core::array::from_fn(|i| HISTOGRAM[i].load(Ordering::Relaxed)),
+    }
+}
+
+/// Runs `f` with statistics reset to zero for the current thread, and
+/// returns its result together with what it allocated and freed.
+///
+/// Scopes nest: an inner scope's activity is also counted by the outer one.
+/// Allocations made on other threads are not counted.
+pub fn scope<R>(f: impl FnOnce() -> R) -> (R, Stats) {
+    struct Restore(Option<Stats>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let inner = SCOPE.with(|slot| slot.replace(self.0.take()));
+            if let Some(inner) = inner {
+                // Fold the inner scope into the outer one
+                with_scope(|outer| {
+                    // The inner peak is relative to where the outer scope stood
+                    outer.peak_bytes = outer.peak_bytes.max(outer.live_bytes + inner.peak_bytes);
+                    outer.live_bytes += inner.live_bytes;
+                    outer.allocations += inner.allocations;
+                    outer.frees += inner.frees;
+                    for (o, i) in outer.histogram.iter_mut().zip(inner.histogram) {
+                        *o += i;
+                    }
+                });
+            }
+        }
+    }
+
+    let restore = Restore(SCOPE.with(|slot| slot.replace(Some(Stats::default()))));
+    let result = f();
+    let stats = snapshot();
+    drop(restore);
+    (result, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(bucket(1), 0);
+        assert_eq!(bucket(4), 2);
+        assert_eq!(bucket(5), 3);
+        assert_eq!(bucket(64), 6);
+        assert_eq!(bucket(usize::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_nested_scopes() {
+        let ((), outer) = scope(|| {
+            record_alloc(8);
+            let ((), inner) = scope(|| record_alloc(16));
+            assert_eq!(inner.allocations, 1);
+            assert_eq!(inner.live_bytes, 16);
+            record_free(16);
+            record_free(8);
+        });
+        assert_eq!(outer.allocations, 2);
+        assert_eq!(outer.peak_bytes, 24);
+        assert_eq!(outer.histogram[bucket(8)], 1);
+        assert!(outer.is_balanced());
+    }
+
+    #[test]
+    fn test_scope_frees_older_allocation() {
+        record_alloc(8);
+        let ((), used) = scope(|| record_free(8));
+        assert_eq!(used.frees, 1);
+        assert_eq!(used.live_allocations(), 0);
+        assert_eq!(used.live_bytes, 0);
+        assert!(!used.is_balanced());
+    }
+}
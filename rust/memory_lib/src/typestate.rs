@@ -0,0 +1,215 @@
+//! Rust Memory Safety Library - Typestate Allocations
+//!
+//! `unsafe_alloc` -> `unsafe_write` -> `unsafe_read` -> `unsafe_free` is a
+//! protocol that only the `# Safety` docs describe. `Allocation<S>` wraps the
+//! same four functions and moves as much of that protocol as possible into
+//! the type system:
+//!
+//! | `# Safety` obligation      | Carried by                          |
+//! |----------------------------|-------------------------------------|
+//! | initialize before reading  | types: `read` exists only on `Init` |
+//! | free with the same `count` | types: `count` lives in the value   |
+//! | no use after free          | types: `free` consumes `self`       |
+//! | no double free             | types: `free` consumes `self`       |
+//! | free eventually            | types: `Drop` frees                 |
+//! | `count > 0`                | runtime: `Allocation::new` checks   |
+//! | `offset < count`           | runtime: `read`/`write` check       |
+//!
+//! Only the last two remain runtime checks; everything else is a compile
+//! error. Reading before initializing:
+//!
+//! ```compile_fail,E0599
+//! use memory_lib::typestate::Allocation;
+//!
+//! let alloc = Allocation::new(4).unwrap();
+//! alloc.read(0);
+//! ```
+//!
+//! Writing after free:
+//!
+//! ```compile_fail,E0382
+//! use memory_lib::typestate::Allocation;
+//!
+//! let mut alloc = Allocation::new(4).unwrap().fill(0);
+//! alloc.free();
+//! alloc.write(0, 1);
+//! ```
+//!
+//! Freeing twice:
+//!
+//! ```compile_fail,E0382
+//! use memory_lib::typestate::Allocation;
+//!
+//! let alloc = Allocation::new(4).unwrap().fill(0);
+//! alloc.free();
+//! alloc.free();
+//! ```
+
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+
+use super::{unsafe_alloc, unsafe_free, unsafe_read, unsafe_write};
+
+/// Allocated, but not yet initialized through the protocol.
+///
+/// `unsafe_alloc` happens to zero-fill, but callers may not rely on that;
+/// the protocol requires a write before the first read.
+pub enum Uninit {}
+
+/// Every element has been written at least once.
+pub enum Init {}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::Uninit {}
+    impl Sealed for super::Init {}
+}
+
+/// The states an `Allocation` can be in.
+pub trait State: sealed::Sealed {}
+impl State for Uninit {}
+impl State for Init {}
+
+/// An `unsafe_alloc` allocation whose protocol state is part of its type.
+///
+/// # Safety Invariants
+///
+/// - `ptr` was returned by `unsafe_alloc(count)` and has not been freed
+/// - In the `Init` state, every element below `count` has been written
+/// - The allocation is freed exactly once, by `free` or by Drop
+pub struct Allocation<S: State> {
+    ptr: *mut i32,
+    count: usize,
+    _state: PhantomData<S>,
+}
+
+impl Allocation<Uninit> {
+    /// Allocates `count` elements; `None` if `count` is zero.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - `unsafe_alloc` requires count > 0: checked here
+    /// - Free with the same count: `count` is stored alongside `ptr`
+    pub fn new(count: usize) -> Option<Self> {
+        if count == 0 {
+            return None;
+        }
+        // SAFETY DISCHARGE: count > 0 checked above
+        let ptr = unsafe { unsafe_alloc(count) };
+        Some(Allocation {
+            ptr,
+            count,
+            _state: PhantomData,
+        })
+    }
+
+    /// Initializes every element with `f(index)`.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Bounds: `i < count` by loop range
+    /// - Ptr live: `self` not yet freed by invariant
+    /// - If `f` panics, `self` is dropped and the memory freed once
+    pub fn init_with(self, mut f: impl FnMut(usize) -> i32) -> Allocation<Init> {
+        for i in 0..self.count {
+            // SAFETY DISCHARGE: i < count, ptr live
+            unsafe { unsafe_write(self.ptr, i, f(i)) };
+        }
+        self.transition()
+    }
+
+    /// Initializes every element to `value`.
+    pub fn fill(self, value: i32) -> Allocation<Init> {
+        self.init_with(|_| value)
+    }
+}
+
+impl Allocation<Init> {
+    /// Checked read.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Initialized: only callable in the `Init` state
+    /// - Bounds: explicit check `offset < count`
+    /// - Ptr live: borrowing `self` proves it has not been freed
+    pub fn read(&self, offset: usize) -> Option<i32> {
+        if offset >= self.count {
+            return None;
+        }
+        // SAFETY DISCHARGE: initialized, in bounds, not freed
+        Some(unsafe { unsafe_read(self.ptr, offset) })
+    }
+
+    /// Checked write.
+    ///
+    /// # Safety Discharge
+    ///
+    /// - Same as `read`
+    /// - No aliasing: &mut self ensures exclusive access
+    pub fn write(&mut self, offset: usize, value: i32) -> Result<(), &'static str> {
+        if offset >= self.count {
+            return Err("Index out of bounds");
+        }
+        // SAFETY DISCHARGE: in bounds, not freed, exclusive via &mut self
+        unsafe { unsafe_write(self.ptr, offset, value) };
+        Ok(())
+    }
+}
+
+impl<S: State> Allocation<S> {
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Frees the allocation. Consuming `self` rules out any later use.
+    pub fn free(self) {
+        drop(self);
+    }
+
+    fn transition<T: State>(self) -> Allocation<T> {
+        let this = ManuallyDrop::new(self);
+        Allocation {
+            ptr: this.ptr,
+            count: this.count,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<S: State> Drop for Allocation<S> {
+    fn drop(&mut self) {
+        // SAFETY DISCHARGE: ptr from unsafe_alloc(count), Drop runs exactly once
+        unsafe { unsafe_free(self.ptr, self.count) };
+    }
+}
+
+// SAFETY: Allocation exclusively owns its memory, like SafeBuffer
+unsafe impl<S: State> Send for Allocation<S> {}
+
+// SAFETY: shared access only reads; mutation requires &mut self
+unsafe impl<S: State> Sync for Allocation<S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        assert!(Allocation::new(0).is_none());
+
+        let alloc = Allocation::new(3).unwrap();
+        let mut alloc = alloc.init_with(|i| i as i32 * 2);
+        assert_eq!(alloc.read(2), Some(4));
+        assert_eq!(alloc.read(3), None);
+
+        alloc.write(0, 9).unwrap();
+        assert!(alloc.write(3, 9).is_err());
+        assert_eq!(alloc.read(0), Some(9));
+        alloc.free();
+    }
+}
@@ -0,0 +1,109 @@
+//! Out-of-memory paths never leak or double-free.
+//!
+//! Each workload is replayed with a failure injected at every allocation in
+//! turn, and under periodic and random schedules. `stats::scope` then checks
+//! that every allocation that succeeded was freed exactly once.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use memory_lib::fault::{self, FaultPlan, FaultReport};
+use memory_lib::secret::SecretBuffer;
+use memory_lib::stats::{self, Stats};
+use memory_lib::typestate::Allocation;
+use memory_lib::{AllocError, SafeBuffer};
+
+/// Allocations made by each workload when nothing fails.
+const ALLOCATIONS: u64 = 5;
+
+/// Uses the panicking constructors.
+fn panicking_workload() -> i32 {
+    let mut a = SafeBuffer::new(4);
+    a.set(0, 1).unwrap();
+    let secret = SecretBuffer::from_slice(&[1, 2, 3]);
+
+    // Spare capacity forces the copying path of From<Vec>
+    let mut vec = Vec::with_capacity(8);
+    vec.extend_from_slice(&[4, 5, 6]);
+    let b = SafeBuffer::from(vec);
+
+    let typed = Allocation::new(2).unwrap().fill(5);
+    let (ptr, len) = SafeBuffer::new(2).into_raw_parts();
+    // SAFETY DISCHARGE: ptr/len from into_raw_parts, adopted once
+    let c = unsafe { SafeBuffer::from_raw_parts(ptr, len) };
+
+    a.get(0).unwrap() + secret.get(2).unwrap() + b.get(0).unwrap() + typed.read(1).unwrap()
+        + c.len() as i32
+}
+
+/// Uses the fallible constructors.
+fn fallible_workload() -> Result<usize, AllocError> {
+    let a = SafeBuffer::try_new(4)?;
+    let secret = SecretBuffer::try_new(3)?;
+    let b = SafeBuffer::try_new(100)?;
+    let c = SafeBuffer::try_new(1)?.into_vec();
+    let d = SafeBuffer::try_new(2)?.into_boxed_slice();
+    let e = SafeBuffer::from(d);
+    Ok(a.len() + secret.len() + b.len() + c.len() + e.len())
+}
+
+fn run<R>(plan: FaultPlan, workload: impl FnOnce() -> R) -> (R, FaultReport, Stats) {
+    let ((result, report), used) = stats::scope(|| fault::inject(plan, workload));
+    (result, report, used)
+}
+
+#[test]
+fn test_workloads_allocate_as_expected() {
+    let (result, report, used) = run(FaultPlan::new(), || catch_unwind(panicking_workload));
+    assert_eq!(result.unwrap(), 1 + 3 + 4 + 5 + 2);
+    assert_eq!(report.attempts, ALLOCATIONS);
+    assert!(used.is_balanced());
+
+    let (result, report, used) = run(FaultPlan::new(), fallible_workload);
+    assert_eq!(result, Ok(110));
+    assert_eq!(report.attempts, ALLOCATIONS);
+    assert!(used.is_balanced());
+}
+
+#[test]
+fn test_panicking_constructors_fail_cleanly_at_every_allocation() {
+    for n in 1..=ALLOCATIONS {
+        let workload = AssertUnwindSafe(panicking_workload);
+        let (result, report, used) = run(FaultPlan::new().fail_nth(n), || catch_unwind(workload));
+        assert!(result.is_err(), "allocation {} should have panicked", n);
+        assert_eq!(report.injected, 1);
+        assert!(used.is_balanced(), "leak after failing allocation {}: {:?}", n, used);
+        assert_eq!(used.allocations, n - 1);
+    }
+}
+
+#[test]
+fn test_fallible_constructors_fail_cleanly_at_every_allocation() {
+    for n in 1..=ALLOCATIONS {
+        let (result, report, used) = run(FaultPlan::new().fail_nth(n), fallible_workload);
+        assert_eq!(result, Err(AllocError::OutOfMemory));
+        assert_eq!(report.attempts, n);
+        assert!(used.is_balanced(), "leak after failing allocation {}: {:?}", n, used);
+    }
+}
+
+#[test]
+fn test_size_threshold_and_schedules() {
+    // Only the 400-byte buffer is above the threshold
+    let (result, report, used) = run(FaultPlan::new().fail_above(64), fallible_workload);
+    assert_eq!(result, Err(AllocError::OutOfMemory));
+    assert_eq!(report.attempts, 3);
+    assert!(used.is_balanced());
+
+    for k in 1..=ALLOCATIONS {
+        let (_, _, used) = run(FaultPlan::new().fail_every(k), fallible_workload);
+        assert!(used.is_balanced());
+    }
+
+    for seed in 0..32 {
+        let plan = FaultPlan::new().fail_randomly(seed, 3);
+        let (_, _, used) = run(plan, fallible_workload);
+        assert!(used.is_balanced(), "leak with seed {}: {:?}", seed, used);
+        let (_, _, used) = run(plan, || catch_unwind(panicking_workload));
+        assert!(used.is_balanced(), "leak with seed {}: {:?}", seed, used);
+    }
+}
@@ -0,0 +1,32 @@
+//! Checks that `memory_lib` builds on `core` + `alloc` alone.
+//!
+//! Building the library for the host with `--no-default-features` makes it
+//! `#![no_std]`, so any leftover `std::` path or `println!` fails the build.
+
+use std::process::Command;
+
+#[test]
+fn test_builds_without_std() {
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    // Separate target dir, so the outer `cargo test` lock is not contended
+    let target_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/no_std-check");
+
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "check",
+            "--offline",
+            "--lib",
+            "--no-default-features",
+            "--manifest-path",
+            manifest,
+        ])
+        .env("CARGO_TARGET_DIR", target_dir)
+        .output()
+        .expect("failed to run cargo");
+
+    assert!(
+        output.status.success(),
+        "no_std build failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,133 @@
+//! Bulk operations stay sound when the user closure panics.
+//!
+//! Every operation is run with a closure that panics at each position in
+//! turn. Afterwards the buffer (if it survives) must still be readable with
+//! the expected mix of old and new values, and `stats::scope` must show that
+//! every allocation was freed exactly once.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use memory_lib::stats;
+use memory_lib::SafeBuffer;
+
+const LEN: usize = 6;
+
+fn original() -> SafeBuffer {
+    SafeBuffer::from(vec![60, 50, 40, 30, 20, 10])
+}
+
+/// Runs `op` on a fresh buffer, and returns the buffer's contents after the
+/// panic together with whether the allocation counts balanced.
+fn run_panicking(op: impl FnOnce(&mut SafeBuffer)) -> (Vec<i32>, bool) {
+    let (contents, used) = stats::scope(|| {
+        let mut buffer = original();
+        let outcome = catch_unwind(AssertUnwindSafe(|| op(&mut buffer)));
+        assert!(outcome.is_err(), "closure was expected to panic");
+        buffer.as_slice().to_vec()
+    });
+    (contents, used.is_balanced())
+}
+
+#[test]
+fn test_map_in_place_panics_at_every_position() {
+    for at in 0..LEN {
+        let (contents, balanced) = run_panicking(|buffer| {
+            let mut seen = 0;
+            buffer.map_in_place(|x| {
+                if seen == at {
+                    panic!("map panics at {}", at);
+                }
+                seen += 1;
+                x + 1
+            });
+        });
+        let expected: Vec<i32> = (0..LEN)
+            .map(|i| original().as_slice()[i] + i32::from(i < at))
+            .collect();
+        assert_eq!(contents, expected);
+        assert!(balanced);
+    }
+}
+
+#[test]
+fn test_fill_with_panics_at_every_position() {
+    for at in 0..LEN {
+        let (contents, balanced) = run_panicking(|buffer| {
+            let mut calls = 0;
+            buffer.fill_with(|| {
+                if calls == at {
+                    panic!("fill panics at {}", at);
+                }
+                calls += 1;
+                -1
+            });
+        });
+        assert_eq!(&contents[..at], &vec![-1; at][..]);
+        assert_eq!(&contents[at..], &original().as_slice()[at..]);
+        assert!(balanced);
+    }
+}
+
+#[test]
+fn test_sort_by_key_panics_at_every_position() {
+    // A sort calls the key function an unspecified number of times
+    for at in 0..LEN * 4 {
+        let mut calls = 0;
+        let ((result, contents), used) = stats::scope(|| {
+            let mut buffer = original();
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                buffer.sort_by_key(|&x| {
+                    if calls == at {
+                        panic!("key panics at {}", at);
+                    }
+                    calls += 1;
+                    x
+                })
+            }));
+            (result, buffer.as_slice().to_vec())
+        });
+
+        // Whether or not it panicked, no element is lost or duplicated
+        let mut sorted = contents.clone();
+        sorted.sort();
+        assert_eq!(sorted, [10, 20, 30, 40, 50, 60]);
+        if result.is_ok() {
+            assert_eq!(contents, sorted);
+        }
+        assert!(used.is_balanced());
+    }
+}
+
+#[test]
+fn test_filtered_panics_at_every_position() {
+    for at in 0..LEN {
+        let ((), used) = stats::scope(|| {
+            let buffer = original();
+            let outcome = catch_unwind(AssertUnwindSafe(|| {
+                let mut seen = 0;
+                buffer.filtered(|x| {
+                    if seen == at {
+                        panic!("filtered panics at {}", at);
+                    }
+                    seen += 1;
+                    x % 20 == 0
+                })
+            }));
+            assert!(outcome.is_err());
+        });
+        // The consumed buffer was freed during unwinding, exactly once
+        assert!(used.is_balanced());
+        assert_eq!(used.allocations, 1);
+    }
+}
+
+#[test]
+fn test_filtered_without_panic_frees_the_original() {
+    let (kept, used) = stats::scope(|| {
+        original()
+            .filtered(|x| x % 20 == 0)
+            .map(|buffer| buffer.as_slice().to_vec())
+    });
+    assert_eq!(kept, Some(vec![60, 40, 20]));
+    assert!(used.is_balanced());
+}
@@ -0,0 +1,80 @@
+//! Proves that `SecretBuffer` wipes its memory before freeing it.
+//!
+//! A recording global allocator copies the contents of one watched block
+//! just before handing it back to the system, so the test can see exactly
+//! what was left behind in freed memory.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use memory_lib::secret::SecretBuffer;
+use memory_lib::SafeBuffer;
+
+const CAPTURE_LEN: usize = 8;
+
+struct RecordingAlloc;
+
+static WATCHED: AtomicUsize = AtomicUsize::new(0);
+static CAPTURED: AtomicBool = AtomicBool::new(false);
+static FREED_BLOCK: [AtomicI32; CAPTURE_LEN] = [const { AtomicI32::new(-1) }; CAPTURE_LEN];
+
+// Both tests share the watch slot, so they must not run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+// SAFETY: every request is forwarded to `System` unchanged; the extra read in
+// `dealloc` happens while the block is still allocated.
+unsafe impl GlobalAlloc for RecordingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ptr as usize == WATCHED.load(Ordering::SeqCst) {
+            let words = ptr as *const i32;
+            for (i, slot) in FREED_BLOCK.iter().enumerate() {
+                slot.store(words.add(i).read_volatile(), Ordering::SeqCst);
+            }
+            CAPTURED.store(true, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: RecordingAlloc = RecordingAlloc;
+
+fn watch(ptr: *const i32) {
+    CAPTURED.store(false, Ordering::SeqCst);
+    WATCHED.store(ptr as usize, Ordering::SeqCst);
+}
+
+fn freed_block() -> [i32; CAPTURE_LEN] {
+    assert!(CAPTURED.load(Ordering::SeqCst), "watched block was never freed");
+    WATCHED.store(0, Ordering::SeqCst);
+    std::array::from_fn(|i| FREED_BLOCK[i].load(Ordering::SeqCst))
+}
+
+#[test]
+fn test_secret_buffer_is_wiped_before_free() {
+    let _guard = SERIAL.lock().unwrap();
+
+    let key = SecretBuffer::from_slice(&[0x1BADB002; CAPTURE_LEN]);
+    watch(key.as_slice().as_ptr());
+    drop(key);
+
+    assert_eq!(freed_block(), [0; CAPTURE_LEN]);
+}
+
+#[test]
+fn test_safe_buffer_leaves_contents_behind() {
+    let _guard = SERIAL.lock().unwrap();
+
+    // Control case: without the wipe, the allocator sees the old data
+    let mut buffer = SafeBuffer::new(CAPTURE_LEN);
+    buffer.as_mut_slice().fill(0x1BADB002);
+    watch(buffer.as_slice().as_ptr());
+    drop(buffer);
+
+    assert_eq!(freed_block(), [0x1BADB002; CAPTURE_LEN]);
+}
@@ -0,0 +1,61 @@
+//! Leak and allocation-count assertions built on `memory_lib::stats`.
+
+use memory_lib::stats;
+use memory_lib::typestate::Allocation;
+use memory_lib::{unsafe_alloc, unsafe_free, SafeBuffer};
+
+#[test]
+fn test_safe_buffer_does_not_leak() {
+    let ((), used) = stats::scope(|| {
+        let mut buffer = SafeBuffer::new(100);
+        buffer.set(0, 1).unwrap();
+        assert_eq!(memory_lib::stats().live_bytes, 400);
+    });
+    assert_eq!(used.allocations, 1);
+    assert_eq!(used.peak_bytes, 400);
+    assert!(used.is_balanced());
+}
+
+#[test]
+fn test_at_most_n_allocations() {
+    let (sum, used) = stats::scope(|| {
+        (1..=4)
+            .map(|len| SafeBuffer::new(len).as_slice().len())
+            .sum::<usize>()
+    });
+    assert_eq!(sum, 10);
+    assert!(used.allocations <= 4);
+    // Sizes 4, 8, 12 and 16 bytes land in buckets 2, 3, 4 and 4
+    assert_eq!(used.histogram[stats::bucket(12)], 2);
+    assert!(used.is_balanced());
+}
+
+#[test]
+fn test_conversions_keep_counts_balanced() {
+    let ((), used) = stats::scope(|| {
+        // Adopted from a Vec, handed back to a Vec
+        let buffer = SafeBuffer::from(vec![1, 2, 3]);
+        assert_eq!(memory_lib::stats().live_bytes, 12);
+        let vec = buffer.into_vec();
+        assert_eq!(memory_lib::stats().live_bytes, 0);
+
+        // Through the raw API and back
+        let (ptr, len) = SafeBuffer::from(vec).into_raw_parts();
+        // SAFETY DISCHARGE: ptr/len from into_raw_parts, freed once
+        unsafe { unsafe_free(ptr, len) };
+    });
+    assert!(used.is_balanced());
+}
+
+#[test]
+fn test_detects_leak() {
+    // SAFETY DISCHARGE: count 2 > 0; freed below
+    let (ptr, used) = stats::scope(|| unsafe { unsafe_alloc(2) });
+    assert_eq!(used.live_allocations(), 1);
+    assert_eq!(used.live_bytes, 8);
+    // SAFETY DISCHARGE: ptr from unsafe_alloc(2), freed once
+    unsafe { unsafe_free(ptr, 2) };
+
+    let ((), used) = stats::scope(|| Allocation::new(2).unwrap().fill(0).free());
+    assert!(used.is_balanced());
+}
@@ -24,50 +24,89 @@ echo ""
 if command -v cargo-geiger &> /dev/null; then
     cargo geiger --quiet 2>/dev/null || cargo geiger
 else
-    echo "Install with: cargo install cargo-geiger"
+    echo "cargo-geiger not installed; using memory_audit geiger (offline):"
     echo ""
-    echo "Example output:"
-    echo "    Metric output format: x/y"
-    echo "    x = unsafe code used by build"
-    echo "    y = total unsafe code found"
-    echo ""
-    echo "    Functions  Expressions  Impls  Traits  Methods  Dependency"
-    echo "    0/0        0/0          0/0    0/0     0/0      memory_lib"
-    echo "    0/0        4/4          0/0    0/0     0/0      └── libc 0.2.x"
+    cargo run --quiet --offline -p memory_audit -- geiger
 fi
 echo ""
 
-# 2. Check for #![forbid(unsafe_code)] in crate roots
+# 2. Check the crates against the declared unsafe policy
 echo "--- Crate-level unsafe policy ---"
-echo "Crates can declare their safety posture in lib.rs/main.rs:"
-echo ""
-for lib in memory_lib/src/lib.rs memory_app/src/main.rs; do
-    if [ -f "$lib" ]; then
-        echo "  $lib:"
-        if grep -q "forbid(unsafe_code)" "$lib"; then
-            echo "    #![forbid(unsafe_code)] - NO unsafe allowed"
-        elif grep -q "deny(unsafe_code)" "$lib"; then
-            echo "    #![deny(unsafe_code)] - unsafe is error (can be overridden)"
-        elif grep -q "warn(unsafe_code)" "$lib"; then
-            echo "    #![warn(unsafe_code)] - unsafe triggers warning"
-        else
-            echo "    (no unsafe policy declared - unsafe allowed)"
-        fi
-    fi
-done
+echo "safety-policy.toml says which crates may contain unsafe code, which"
+echo "modules may define unsafe fn, and which must stay safe. Crate roots must"
+echo "declare the #![...(unsafe_code)] level the policy requires:"
+echo ""
+cargo run --quiet --offline -p memory_audit -- policy
+echo ""
+
+# 3. Count unsafe code in the workspace
+echo "--- Unsafe code count ---"
+echo "Parsed from syntax trees (comments and strings are ignored):"
+echo ""
+cargo run --quiet --offline -p memory_audit -- count
+echo ""
+
+# 4. Ratchet: unsafe counts may only shrink
+echo "--- Unsafe-count baseline ---"
+echo "Counts per module and item in memory_lib/src must not rise above"
+echo "memory_lib/safety-baseline.json. After a reviewed, intended change, record"
+echo "it with: cargo run -p memory_audit -- baseline --update-baseline"
+echo ""
+cargo run --quiet --offline -p memory_audit -- baseline
+echo ""
+
+# 5. Check the safety comment conventions
+echo "--- Safety comments ---"
+echo "Every unsafe block needs a SAFETY DISCHARGE comment, every unsafe fn a"
+echo "# Safety doc section, and every unsafe impl a SAFETY comment:"
+echo ""
+cargo run --quiet --offline -p memory_audit -- check
+echo ""
+
+# 6. Find leaky suppressions
+echo "--- Leaky suppressions ---"
+echo "Safe public functions that hand raw pointers across their signature."
+echo "into_raw_parts and propagation_chain::level3_suppress are expected here:"
+echo ""
+cargo run --quiet --offline -p memory_audit -- leaks || true
+echo ""
+
+# 7. Check the reviewed-suppression ledger
+echo "--- Safety ledger ---"
+echo "Every unsafe block and impl in memory_lib/src must match a reviewed entry"
+echo "in memory_lib/safety-ledger.toml; changed or unreviewed code fails the"
+echo "audit. After reviewing an item, record it with:"
+echo "  cargo run -p memory_audit -- ledger --record REVIEWER --item PATH"
+echo ""
+cargo run --quiet --offline -p memory_audit -- ledger
+echo ""
+
+# 8. Read the safety summaries back out of the built binary
+echo "--- Embedded safety metadata ---"
+echo "With the safety-summary feature, each crate's build script stores its unsafe"
+echo "counts, policy and ledger hash in the .msafety section, so a shipped binary"
+echo "can be audited without source:"
+echo ""
+if [ "$(uname -s)" = "Linux" ]; then
+    # Ask cargo where the binary went, so CARGO_TARGET_DIR is honoured
+    memory_app=$(cargo build --quiet --offline -p memory_app --features safety-summary \
+        --message-format=json | sed -n 's/.*"executable":"\([^"]*\)".*/\1/p' | tail -n 1)
+    cargo run --quiet --offline -p memory_audit -- embedded "$memory_app"
+else
+    echo "Skipped: memory_audit embedded only reads ELF binaries."
+fi
 echo ""
 
-# 3. Count unsafe blocks in the codebase
-echo "--- Unsafe block count ---"
-echo "Direct search for unsafe usage in this workspace:"
+# 9. Write every finding as SARIF for code-scanning dashboards
+echo "--- SARIF ---"
+echo "All findings above, with stable rule ids and suggested fixes:"
 echo ""
-unsafe_count=$(grep -r "unsafe\s*{" --include="*.rs" . 2>/dev/null | wc -l | tr -d ' ')
-unsafe_fn_count=$(grep -r "unsafe\s*fn" --include="*.rs" . 2>/dev/null | wc -l | tr -d ' ')
-echo "  unsafe { } blocks: $unsafe_count"
-echo "  unsafe fn declarations: $unsafe_fn_count"
+mkdir -p target
+cargo run --quiet --offline -p memory_audit -- sarif > target/memory_audit.sarif
+echo "  wrote target/memory_audit.sarif"
 echo ""
 
-# 4. cargo-audit: Check for known vulnerabilities
+# 10. cargo-audit: Check for known vulnerabilities
 echo "--- cargo-audit: Security vulnerabilities ---"
 echo "Checks dependencies against RustSec Advisory Database."
 echo ""
@@ -87,7 +126,8 @@ echo "=== Summary ==="
 echo ""
 echo "Rust provides strong auditing through:"
 echo "  1. cargo-geiger: Quantifies unsafe in entire dependency tree"
-echo "  2. Crate attributes: #![forbid(unsafe_code)] enforced at compile time"
+echo "  2. Crate attributes: #![forbid(unsafe_code)] enforced at compile time,"
+echo "     checked against safety-policy.toml"
 echo "  3. Crate metadata: Safety posture visible in Cargo.toml and docs"
 echo "  4. cargo-audit: CVE tracking for dependencies"
 echo ""
@@ -0,0 +1,18 @@
+#!/bin/bash
+# Runs the tests in every feature configuration of memory_lib.
+#
+# A plain `cargo test` only covers the default features. The statistics and
+# fault-injection tests need their features, and the featureless build has
+# to keep working without them.
+
+set -e
+cd "$(dirname "$0")/.."
+
+echo "--- Default features ---"
+cargo test --quiet --offline --workspace
+
+echo "--- No features ---"
+cargo test --quiet --offline -p memory_lib --no-default-features
+
+echo "--- All features ---"
+cargo test --quiet --offline -p memory_lib --all-features